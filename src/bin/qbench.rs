@@ -1,36 +1,512 @@
-use anyhow::Result;
-use clap::Parser;
+use std::{env, io, process};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser};
 use console::{style, Term};
-use tabled::{settings::Style, Table};
+use tabled::Table;
+use tokio::signal;
 
-use qbench::args::Args;
+use qbench::args::{Args, Command, ExportFormat, FailOn};
 use qbench::bench::QBench;
-use qbench::util;
+use qbench::compare::{self, TargetResults};
+use qbench::container::{self, Container};
+use qbench::error::QBenchError;
+#[cfg(feature = "otel")]
+use qbench::telemetry::Telemetry;
+use qbench::history::{self, HistoryEntry};
+use qbench::util::{RunMetadata, RunTally};
+use qbench::schedule::{self, Schedule};
+use qbench::upload::S3Upload;
+use qbench::{
+    capture, coordinator, criterion, init, markdown, remote, report, seed, server, slowlog, util,
+    RevisionStatus,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
     let term = Term::stdout();
-    term.write_line("Running benchmarks...")?;
+
+    // Export the run to an OpenTelemetry collector when asked to, if built with support for it
+    #[cfg(feature = "otel")]
+    let telemetry = args.otlp_endpoint.as_deref().map(Telemetry::init).transpose()?;
+    #[cfg(feature = "otel")]
+    let layers = telemetry.iter().map(Telemetry::layer).collect();
+    #[cfg(not(feature = "otel"))]
+    let layers = {
+        if args.otlp_endpoint.is_some() {
+            anyhow::bail!("--otlp-endpoint requires qbench to be built with the otel feature");
+        }
+        vec![]
+    };
+
+    if args.fail_on.contains(&FailOn::Regression) && args.history.is_none() {
+        anyhow::bail!("--fail-on regression requires --history to compare with the previous run");
+    }
+
+    #[cfg(not(feature = "charts"))]
+    if args.export == ExportFormat::Chart {
+        anyhow::bail!("--export chart requires qbench to be built with the charts feature");
+    }
+
+    if args.out_dir.is_some() && !matches!(args.export, ExportFormat::Json | ExportFormat::Toml) {
+        anyhow::bail!("--out-dir requires --export json or toml");
+    }
+
+    let upload = S3Upload::new(&args)?;
+
+    if let Some(Command::Init { dir, force }) = &args.command {
+        for path in init::scaffold(dir, *force)? {
+            term.write_line(&format!("Created {}", path.display()))?;
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Completions { shell }) = &args.command {
+        clap_complete::generate(*shell, &mut Args::command(), "qbench", &mut io::stdout());
+        return Ok(());
+    }
+
+    if let Some(Command::ImportLog { log, file, top, force }) = &args.command {
+        let content = std::fs::read_to_string(log)
+            .with_context(|| format!("Error reading {}", log.display()))?;
+        let statements = slowlog::aggregate(slowlog::parse_log(&content), *top);
+        let source = format!("the slow-query log {}", log.display());
+        capture::write_config(file, &capture::render_config(&statements, &source), *force)?;
+        term.write_line(&format!(
+            "Imported {} statements into {}",
+            statements.len(),
+            file.display()
+        ))?;
+        return Ok(());
+    }
+
+    // Read before connecting, so the other subcommands and dry runs never prompt
+    if !args.dry_run {
+        util::read_password(&mut args)?;
+    }
+    // Once the password is known, so the log hides it as well
+    util::init_logging(&args, layers)?;
+    util::set_table_style(args.table_style);
+
+    // Resolve what would run without connecting, prompting for a password or starting anything
+    if args.dry_run {
+        if args.command.is_some() {
+            anyhow::bail!("--dry-run only applies to benchmark runs");
+        }
+        let qbench = QBench::new(args, false).await?;
+        let plan = qbench.plan().await?;
+        term.write_line(&qbench.redact(&plan.to_string()))?;
+        return Ok(());
+    }
+
+    if let Some(Command::Seed { spec, truncate }) = &args.command {
+        let spec = seed::load_spec(spec)?;
+        let mut rng = seed::rng(&spec);
+        let options = util::connect_options(&args, &args.url)?;
+        let mut conn = util::connect(&options, args.schema.as_deref())
+            .await
+            .with_context(|| format!("Error connecting to {}", util::redact_url(&args.url)))?;
+        for table in &spec.tables {
+            let duration = seed::seed_table(&mut conn, table, &mut rng, *truncate).await?;
+            term.write_line(&format!(
+                "Seeded {} with {} rows in {}",
+                table.name,
+                table.rows,
+                util::format_duration_pretty(&duration)
+            ))?;
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Capture { file, top, force }) = &args.command {
+        let options = util::connect_options(&args, &args.url)?;
+        let mut conn = util::connect(&options, args.schema.as_deref())
+            .await
+            .with_context(|| format!("Error connecting to {}", util::redact_url(&args.url)))?;
+        let statements = capture::top_statements(&mut conn, *top).await?;
+        let source = format!("pg_stat_statements of {}", util::redact_url(&args.url));
+        let config = capture::render_config(&statements, &source);
+        capture::write_config(file, &config, *force)?;
+        term.write_line(&format!(
+            "Captured {} statements into {}",
+            statements.len(),
+            file.display()
+        ))?;
+        return Ok(());
+    }
+
+    if args.remote.is_some() && args.command.is_some() {
+        anyhow::bail!("--remote only applies to benchmark runs");
+    }
+    if !args.targets.is_empty() && args.command.is_some() {
+        anyhow::bail!("--target only applies to benchmark runs");
+    }
+
+    // Kept until qbench exits, the container is removed when dropped
+    let container = match &args.container {
+        Some(image) => {
+            term.write_line(&format!("Starting a container of {}...", image))?;
+            let container = Container::start(image).await?;
+            term.clear_last_lines(1)?;
+            term.write_line(&format!("Started a container of {}", image))?;
+            args.url = container.url.clone();
+            Some(container)
+        }
+        None => None,
+    };
+
     let mut qbench = QBench::new(args, true).await?;
-    let bench_res = qbench.run_bench().await;
-    term.clear_last_lines(1)?;
 
-    match (bench_res, qbench.args.export.to_lowercase().as_str()) {
-        (Ok(bench_res), "json") => {
-            util::export_json(&term, &qbench, &bench_res)?;
+    // Fail fast on connection problems, before anything is parsed or timed. A remote run
+    // connects from the remote host, this one may not reach the database at all.
+    let mut target = util::redact_url(&qbench.args.url);
+    if qbench.args.remote.is_none() {
+        term.write_line("Connecting to the database...")?;
+        let version = match qbench.preflight().await {
+            Ok(version) => version,
+            Err(e) => {
+                term.clear_last_lines(1)?;
+                let e = anyhow::Error::from(e);
+                term.write_line(style(format!("{:?}", e)).red().to_string().as_str())?;
+                if qbench.args.fail_on.contains(&FailOn::Never) {
+                    return Ok(());
+                }
+                // Exiting skips the destructors
+                drop(container);
+                process::exit(1);
+            }
+        };
+        term.clear_last_lines(1)?;
+        term.write_line(&format!("Connected to {}", version))?;
+        target = compare::short_version(&version);
+    }
+
+    if let Some(Command::Serve { listen }) = qbench.args.command {
+        term.write_line(&format!("Serving the API on http://{}", listen))?;
+        return server::serve(qbench, listen).await;
+    }
+
+    // Interrupt the run on the first Ctrl-C so partial results are still reported,
+    // and give up immediately on the second one.
+    let cancel = qbench.cancellation_token();
+    let container_id = container.as_ref().map(|container| container.id().to_string());
+    tokio::spawn(async move {
+        if signal::ctrl_c().await.is_ok() {
+            cancel.cancel();
+            if signal::ctrl_c().await.is_ok() {
+                // Exiting skips the destructors, the container is removed first
+                if let Some(id) = &container_id {
+                    container::remove(id);
+                }
+                process::exit(130);
+            }
+        }
+    });
+
+    if let Some(Command::Schedule {
+        every,
+        cron,
+        notify,
+        drift_threshold,
+    }) = qbench.args.command.clone()
+    {
+        let schedule = match (every, cron) {
+            (Some(every), _) => Schedule::Every(every),
+            (_, Some(cron)) => Schedule::Cron(Box::new(schedule::parse_cron(&cron)?)),
+            (None, None) => unreachable!("clap requires --every or --cron"),
+        };
+        return schedule::run(
+            &mut qbench,
+            &term,
+            &schedule,
+            notify.as_ref(),
+            drift_threshold,
+        )
+        .await;
+    }
+
+    // Probe the benchmarks to tell how long the run will take, and whether it's worth it
+    if qbench.args.estimate || qbench.args.confirm_above.is_some() {
+        if qbench.args.remote.is_some() || qbench.args.command.is_some() {
+            anyhow::bail!("--estimate and --confirm-above only apply to local benchmark runs");
+        }
+        term.write_line("Estimating the duration of the run...")?;
+        let estimate = qbench.estimate().await;
+        term.clear_last_lines(1)?;
+        let estimate = match estimate {
+            Ok(estimate) => estimate,
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                anyhow::bail!("{}", qbench.redact(&format!("{:#}", e)));
+            }
+        };
+        term.write_line(&qbench.redact(&estimate.to_string()))?;
+        if let Some(threshold) = qbench.args.confirm_above.filter(|t| estimate.total > *t) {
+            let threshold = humantime::format_duration(threshold);
+            if !term.is_term() {
+                anyhow::bail!("The run is estimated to take longer than {}, aborting", threshold);
+            }
+            term.write_str(&format!("That's longer than {}, run anyway? [y/N] ", threshold))?;
+            let answer = term.read_line()?;
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                term.write_line("Run cancelled.")?;
+                return Ok(());
+            }
+        }
+    }
+
+    // The lines are written as the benchmarks complete, unless every result is needed at the
+    // end of the run anyway
+    let args = &qbench.args;
+    if args.export == ExportFormat::Jsonl
+        && args.remote.is_none()
+        && args.history.is_none()
+        && args.targets.is_empty()
+        && args.gha_summary.is_none()
+        && !matches!(args.command, Some(Command::Coordinate { .. }))
+    {
+        return run_jsonl(&term, &qbench, upload.as_ref(), target, container).await;
+    }
+
+    let started_at = util::timestamp();
+    let start = Instant::now();
+    let mut bench_res = if let Some(host) = &qbench.args.remote {
+        // The remote run shows its own progress
+        let argv: Vec<String> = env::args().skip(1).collect();
+        remote::run(&qbench, host, &argv)
+            .await
+            .map_err(QBenchError::from)
+    } else {
+        term.write_line("Running benchmarks...")?;
+        let bench_res = match &qbench.args.command {
+            Some(Command::Coordinate { workers }) => coordinator::run(&qbench, workers)
+                .await
+                .map_err(QBenchError::from),
+            _ => qbench.run_bench().await,
+        };
+        term.clear_last_lines(1)?;
+        bench_res
+    };
+
+    // Tell the noise of each revision apart, from the earlier runs of the history if any, and
+    // how precise its statistics are
+    let past = match &qbench.args.history {
+        Some(path) => history::load(path)?,
+        None => vec![],
+    };
+    if let Ok(results) = &mut bench_res {
+        history::estimate_noise(results, &past);
+        util::estimate_confidence(results, qbench.args.resamples);
+    }
+
+    // Record complete runs only, partial ones aren't comparable with the others
+    if let (Ok(results), Some(path)) = (&bench_res, &qbench.args.history) {
+        if !qbench.is_interrupted() {
+            let entry = HistoryEntry {
+                started_at: started_at.clone(),
+                results: results.clone(),
+            };
+            history::append(path, &entry)?;
+        }
+    }
+
+    #[cfg(feature = "otel")]
+    if let Some(telemetry) = telemetry {
+        if let Ok(results) = &bench_res {
+            telemetry.record_results(results);
+        }
+        if let Err(e) = telemetry.shutdown() {
+            term.write_line(style(format!("{:#}", e)).yellow().to_string().as_str())?;
+        }
+    }
+
+    // Run the same suite against the databases it is compared with, one after the other
+    let mut targets = vec![];
+    if let (false, Ok(results)) = (qbench.args.targets.is_empty(), &bench_res) {
+        targets.push(TargetResults {
+            target: target.clone(),
+            results: results.clone(),
+        });
+        for url in &qbench.args.targets {
+            if qbench.is_interrupted() {
+                break;
+            }
+            term.write_line(&format!("Running benchmarks on {}...", util::redact_url(url)))?;
+            let result = compare::run_target(&qbench.args, url, qbench.cancellation_token()).await;
+            term.clear_last_lines(1)?;
+            match result {
+                Ok(result) => targets.push(result),
+                Err(e) => {
+                    let e = e.context(format!("Error comparing with {}", util::redact_url(url)));
+                    let message = qbench.redact(&format!("{:?}", e)).into_owned();
+                    term.write_line(style(message).red().to_string().as_str())?;
+                }
+            }
+        }
+    }
+
+    let aborted = qbench.abort_reason();
+    if let Some(reason) = &aborted {
+        let message = format!("Run aborted, {}. Results are partial.", reason);
+        term.write_line(style(message).red().to_string().as_str())?;
+    } else if qbench.is_interrupted() {
+        term.write_line(
+            style("Run interrupted, results are partial.")
+                .yellow()
+                .to_string()
+                .as_str(),
+        )?;
+    }
+
+    // Count the revisions violating their latency objectives or failing, and those slower than
+    // in the previous run, before the results are consumed
+    let slo_violations = bench_res.as_ref().map_or(0, |res| {
+        res.iter()
+            .flat_map(|bench| &bench.results)
+            .filter(|rev| rev.status == RevisionStatus::SloViolated)
+            .count()
+    });
+    // A run aborted by a guardrail fails like a failed revision
+    let failed = aborted.is_some() || bench_res.as_ref().map_or(true, |res| {
+        res.iter().flat_map(|bench| &bench.results).any(|rev| {
+            matches!(
+                rev.status,
+                RevisionStatus::Failed | RevisionStatus::TimedOut | RevisionStatus::RowLimitExceeded
+            )
+        })
+    });
+    let regressions = match (&bench_res, past.last()) {
+        (Ok(res), Some(previous)) => {
+            schedule::detect_drift(&previous.results, res, qbench.args.regression_threshold)
+        }
+        _ => vec![],
+    };
+
+    // Summarize the run before the results are consumed, to print it after everything else
+    let run_summary = bench_res
+        .as_ref()
+        .ok()
+        .map(|res| util::format_run_summary(res, start.elapsed()));
+
+    // Show the results on the page of the workflow run when running on GitHub Actions
+    if let (Ok(res), Some(summary), Some(path)) =
+        (&bench_res, &run_summary, &qbench.args.gha_summary)
+    {
+        let group_by = qbench.args.group_by;
+        markdown::append(path, &markdown::report(res, &regressions, summary, group_by))?;
+    }
+
+    // A webhook being down doesn't fail the run, its results are already in
+    if let Some(url) = &qbench.args.notify_url {
+        let error = match &bench_res {
+            Err(e) => Some(qbench.redact(&e.to_string()).into_owned()),
+            Ok(_) if aborted.is_some() => aborted.as_ref().map(|r| format!("Run aborted, {}", r)),
+            Ok(res) if failed => {
+                let count = res.iter().flat_map(|bench| &bench.results).filter(|rev| {
+                    matches!(
+                        rev.status,
+                        RevisionStatus::Failed
+                            | RevisionStatus::TimedOut
+                            | RevisionStatus::RowLimitExceeded
+                    )
+                });
+                Some(format!("{} revisions failed", count.count()))
+            }
+            Ok(_) => None,
+        };
+        let format = qbench.args.notify_format;
+        let overview = run_summary.as_deref();
+        let notified =
+            schedule::notify(url, format, &started_at, error, &regressions, overview).await;
+        if let Err(e) = notified {
+            tracing::warn!(error = %format!("{:#}", e), "notification failed");
+            term.write_line(style(format!("{:#}", e)).yellow().to_string().as_str())?;
+        }
+    }
+
+    let metadata = RunMetadata::new(&qbench.args, started_at, target, qbench.run_id());
+    let exported = bench_res.is_ok();
+    match (bench_res, qbench.args.export) {
+        (Ok(bench_res), ExportFormat::Json) => {
+            util::export_json(&term, &qbench, &bench_res, &targets, &metadata)?;
         }
-        (Ok(bench_res), "toml") => {
-            util::export_toml(&term, &qbench, &bench_res)?;
+        (Ok(bench_res), ExportFormat::Toml) => {
+            util::export_toml(&term, &qbench, &bench_res, &targets, &metadata)?;
+        }
+        (Ok(bench_res), ExportFormat::Jsonl) => {
+            util::export_jsonl(&term, &qbench, &bench_res, &targets, &metadata)?;
+        }
+        (Ok(bench_res), ExportFormat::Http) => {
+            util::export_http(&term, &qbench, &bench_res, &targets, &metadata).await?;
+        }
+        (Ok(bench_res), ExportFormat::Criterion) => {
+            criterion::export(&term, &qbench, &bench_res)?;
+        }
+        (Ok(bench_res), ExportFormat::Bencher) => {
+            util::export_bencher(&term, &qbench, &bench_res)?;
+        }
+        (Ok(bench_res), ExportFormat::Gitlab) => {
+            util::export_gitlab(&term, &qbench, &bench_res)?;
+        }
+        (Ok(bench_res), ExportFormat::Badge) => {
+            util::export_badges(&term, &qbench, &bench_res)?;
+        }
+        (Ok(bench_res), ExportFormat::Html) => {
+            report::export(&term, &qbench, &bench_res, &metadata)?;
+        }
+        #[cfg(feature = "charts")]
+        (Ok(bench_res), ExportFormat::Chart) => {
+            qbench::chart::export(&term, &qbench, &bench_res)?;
         }
         (Ok(bench_res), _) => {
-            let mut table = Table::new(bench_res);
-            table.with(Style::modern());
-            term.write_line(&table.to_string())?;
+            let mut speedups: Vec<String> = vec![];
+            match qbench.args.group_by {
+                // A table per group, followed by the comparisons of its benchmarks and its summary
+                Some(group_by) => {
+                    for group in util::group_results(&bench_res, group_by) {
+                        term.write_line(&style(&group.name).bold().to_string())?;
+                        let mut table = Table::new(group.benches.iter().copied());
+                        util::style_table(&mut table);
+                        term.write_line(&table.to_string())?;
+                        for speedup in group.benches.iter().flat_map(|b| util::format_speedups(b)) {
+                            term.write_line(&speedup)?;
+                        }
+                        for line in util::format_group_summary(&group) {
+                            term.write_line(&line)?;
+                        }
+                        term.write_line("")?;
+                    }
+                }
+                None => {
+                    speedups.extend(bench_res.iter().flat_map(util::format_speedups));
+                    let mut table = Table::new(&bench_res);
+                    util::style_table(&mut table);
+                    term.write_line(&table.to_string())?;
+                }
+            }
+            speedups.extend(util::format_geometric_means("suite", &bench_res));
+
+            // Point out when the numbers may include waiting on qbench's own pool, which the
+            // workers use instead when coordinating them
+            if !matches!(qbench.args.command, Some(Command::Coordinate { .. })) {
+                let pool_stats = qbench.pool_stats();
+                let line = if pool_stats.is_saturated() {
+                    style(format!("{} (saturated)", pool_stats)).yellow().to_string()
+                } else {
+                    pool_stats.to_string()
+                };
+                term.write_line(&line)?;
+            }
+            for speedup in speedups {
+                term.write_line(&speedup)?;
+            }
         }
         (Err(e), _) => {
+            let e = anyhow::Error::from(e);
             term.write_line(
-                style(format!("{:?}", e).as_str())
+                style(qbench.redact(&format!("{:?}", e)))
                     .red()
                     .to_string()
                     .as_str(),
@@ -38,5 +514,162 @@ async fn main() -> Result<()> {
         }
     }
 
+    if let (true, Some(upload)) = (exported, &upload) {
+        upload.upload(&term, &qbench, &metadata).await?;
+    }
+
+    if targets.len() > 1 {
+        term.write_line(&compare::comparison_table(&targets).to_string())?;
+    }
+
+    if let Some(summary) = run_summary {
+        term.write_line(&summary)?;
+    }
+
+    if !regressions.is_empty() {
+        let list: Vec<String> = regressions
+            .iter()
+            .map(|r| format!("{}/{} {:+.1}%", r.bench, r.revision, r.change_pct))
+            .collect();
+        term.write_line(
+            style(format!("Regressions since the previous run: {}", list.join(", ")))
+                .red()
+                .to_string()
+                .as_str(),
+        )?;
+    }
+
+    if slo_violations > 0 {
+        term.write_line(
+            style(format!("{} revisions violated their latency objectives.", slo_violations))
+                .red()
+                .to_string()
+                .as_str(),
+        )?;
+    }
+
+    let fail_on = |outcome: FailOn| qbench.args.fail_on.contains(&outcome);
+    let fail = (failed && fail_on(FailOn::Error))
+        || (!regressions.is_empty() && fail_on(FailOn::Regression))
+        || (slo_violations > 0 && (fail_on(FailOn::Slo) || qbench.args.fail_on_slo));
+    if fail && !fail_on(FailOn::Never) {
+        // Exiting skips the destructors
+        drop(container);
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Runs the benchmarks with `--export jsonl`, writing the result of each of them away as soon as
+/// it completes instead of holding them all for the reports, which only get the summary of the
+/// run.
+///
+/// # Arguments
+///
+/// * `term` - The terminal the progress and summary are written to.
+/// * `qbench` - The instance configured by the command line.
+/// * `upload` - Where the export is uploaded to, if anywhere.
+/// * `target` - The database, as described by the metadata of the run.
+/// * `container` - The container of the database, removed before exiting with a failure.
+async fn run_jsonl(
+    term: &Term,
+    qbench: &QBench,
+    upload: Option<&S3Upload>,
+    target: String,
+    container: Option<Container>,
+) -> Result<()> {
+    let started_at = util::timestamp();
+    let start = Instant::now();
+    let mut tally = RunTally::default();
+    let metadata = RunMetadata::new(&qbench.args, started_at.clone(), target, qbench.run_id());
+    term.write_line("Running benchmarks...")?;
+    let exported = util::stream_jsonl(qbench, &metadata, &mut tally).await;
+    term.clear_last_lines(1)?;
+
+    let aborted = qbench.abort_reason();
+    if let Some(reason) = &aborted {
+        let message = format!("Run aborted, {}. Results are partial.", reason);
+        term.write_line(style(message).red().to_string().as_str())?;
+    } else if qbench.is_interrupted() {
+        term.write_line(
+            style("Run interrupted, results are partial.")
+                .yellow()
+                .to_string()
+                .as_str(),
+        )?;
+    }
+
+    let failed = aborted.is_some() || exported.is_err() || tally.errors() > 0;
+    let run_summary = tally.summary(start.elapsed());
+
+    // A webhook being down doesn't fail the run, its results are already in
+    if let Some(url) = &qbench.args.notify_url {
+        let error = match &exported {
+            Err(e) => Some(qbench.redact(&format!("{:#}", e)).into_owned()),
+            Ok(()) if aborted.is_some() => aborted.as_ref().map(|r| format!("Run aborted, {}", r)),
+            Ok(()) if failed => Some(format!("{} revisions failed", tally.errors())),
+            Ok(()) => None,
+        };
+        let format = qbench.args.notify_format;
+        let notified =
+            schedule::notify(url, format, &started_at, error, &[], Some(&run_summary)).await;
+        if let Err(e) = notified {
+            tracing::warn!(error = %format!("{:#}", e), "notification failed");
+            term.write_line(style(format!("{:#}", e)).yellow().to_string().as_str())?;
+        }
+    }
+
+    match &exported {
+        Ok(()) => term.write_line("Results exported to JSON Lines.")?,
+        Err(e) => term.write_line(
+            style(qbench.redact(&format!("{:?}", e)))
+                .red()
+                .to_string()
+                .as_str(),
+        )?,
+    }
+
+    if let (Ok(()), Some(upload)) = (&exported, upload) {
+        let metadata = RunMetadata {
+            finished_at: util::timestamp(),
+            ..metadata
+        };
+        upload.upload(term, qbench, &metadata).await?;
+    }
+
+    let pool_stats = qbench.pool_stats();
+    let line = if pool_stats.is_saturated() {
+        style(format!("{} (saturated)", pool_stats))
+            .yellow()
+            .to_string()
+    } else {
+        pool_stats.to_string()
+    };
+    term.write_line(&line)?;
+    term.write_line(&run_summary)?;
+
+    let slo_violations = tally.slo_violations();
+    if slo_violations > 0 {
+        term.write_line(
+            style(format!(
+                "{} revisions violated their latency objectives.",
+                slo_violations
+            ))
+            .red()
+            .to_string()
+            .as_str(),
+        )?;
+    }
+
+    let fail_on = |outcome: FailOn| qbench.args.fail_on.contains(&outcome);
+    let fail = (failed && fail_on(FailOn::Error))
+        || (slo_violations > 0 && (fail_on(FailOn::Slo) || qbench.args.fail_on_slo));
+    if fail && !fail_on(FailOn::Never) {
+        // Exiting skips the destructors
+        drop(container);
+        process::exit(1);
+    }
+
     Ok(())
 }
@@ -0,0 +1,178 @@
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use sqlx::AnyPool;
+use tokio_util::sync::CancellationToken;
+
+use crate::args::{Args, ExecutionMode, OnError, QueryProtocol};
+use crate::bench::QBench;
+use crate::error::QBenchError;
+use crate::parser::DefaultParser;
+use crate::QueryBenchParser;
+
+/// Builds a [`QBench`] from code, starting from the same defaults as the command line.
+///
+/// Settings without a dedicated method can be changed by passing complete [`Args`] with
+/// [`QBenchBuilder::args`].
+#[derive(Debug)]
+pub struct QBenchBuilder {
+    args: Args,
+    pool: Option<AnyPool>,
+    parser: DefaultParser,
+    cancel: Option<CancellationToken>,
+    display_progress: bool,
+}
+
+impl Default for QBenchBuilder {
+    fn default() -> Self {
+        Self {
+            // Only the defaults declared on the arguments, the process arguments are not read
+            args: Args::parse_from(["qbench"]),
+            pool: None,
+            parser: DefaultParser::new(),
+            cancel: None,
+            display_progress: false,
+        }
+    }
+}
+
+impl QBenchBuilder {
+    /// Replaces all settings with the given arguments.
+    pub fn args(mut self, args: Args) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Sets the database connection URL.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.args.url = url.into();
+        self
+    }
+
+    /// Sets the connection URL of the read replica benchmarks with `target = "replica"` run
+    /// against, see `--replica-url`. Only used when qbench creates its pools from the URLs.
+    pub fn replica_url(mut self, url: impl Into<String>) -> Self {
+        self.args.replica_url = Some(url.into());
+        self
+    }
+
+    /// Sets the schema queries run against, see `--schema`.
+    pub fn schema(mut self, schema: impl Into<String>) -> Self {
+        self.args.schema = Some(schema.into());
+        self
+    }
+
+    /// Sets the directory the benchmark config files are loaded from.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.args.dir = dir.into();
+        self
+    }
+
+    /// Sets the pattern config files are matched with, e.g. `*.toml` or `**/*.toml`.
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.args.filter = vec![filter.into()];
+        self
+    }
+
+    /// Only runs the benchmarks whose name matches this glob pattern.
+    pub fn bench(mut self, pattern: impl Into<String>) -> Self {
+        self.args.bench = Some(pattern.into());
+        self
+    }
+
+    /// Sets the number of iterations of each revision.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.args.iterations = iterations;
+        self
+    }
+
+    /// Sets the maximum number of connections of the pool created by qbench, or of the pool
+    /// given with [`QBenchBuilder::pool`] when reporting its saturation.
+    pub fn max_connections(mut self, max_connections: u32) -> Self {
+        self.args.max_connections = max_connections;
+        self
+    }
+
+    /// Sets the time an iteration may take before the revision is marked as timed out.
+    pub fn query_timeout(mut self, timeout: Duration) -> Self {
+        self.args.query_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the number of times an iteration failing with a transient error is retried.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.args.retries = retries;
+        self
+    }
+
+    /// Sets whether benchmarks run concurrently or one at a time.
+    pub fn mode(mut self, mode: ExecutionMode) -> Self {
+        self.args.mode = mode;
+        self
+    }
+
+    /// Sets the maximum number of benchmarks running at the same time.
+    pub fn parallelism(mut self, parallelism: NonZeroUsize) -> Self {
+        self.args.parallelism = Some(parallelism);
+        self
+    }
+
+    /// Sets what to do when a revision fails.
+    pub fn on_error(mut self, on_error: OnError) -> Self {
+        self.args.on_error = on_error;
+        self
+    }
+
+    /// Sets the protocol queries are executed with.
+    pub fn protocol(mut self, protocol: QueryProtocol) -> Self {
+        self.args.protocol = protocol;
+        self
+    }
+
+    /// Runs the benchmarks on an existing pool instead of creating one from the URL.
+    ///
+    /// Connections opened outside of the pool, e.g. for `--cold-connection`, use the pool's
+    /// connect options. The schema is not set on the pool's connections when they connect,
+    /// use the pool's own `after_connect` hook for that.
+    pub fn pool(mut self, pool: AnyPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Registers a parser for the config files with the given extension, see
+    /// [`DefaultParser::register`].
+    pub fn parser(mut self, extension: &str, parser: impl QueryBenchParser + 'static) -> Self {
+        self.parser.register(extension, parser);
+        self
+    }
+
+    /// Interrupts the run when the given token is cancelled, see
+    /// [`QBench::set_cancellation_token`].
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Sets whether progress is displayed while running.
+    pub fn display_progress(mut self, display_progress: bool) -> Self {
+        self.display_progress = display_progress;
+        self
+    }
+
+    /// Creates the configured [`QBench`].
+    pub async fn build(self) -> Result<QBench, QBenchError> {
+        let mut qbench = match self.pool {
+            Some(pool) => QBench::with_pool(pool, self.args, self.display_progress)?,
+            None => QBench::new(self.args, self.display_progress).await?,
+        };
+        qbench.parser = Arc::new(self.parser);
+        if let Some(token) = self.cancel {
+            qbench.set_cancellation_token(token);
+        }
+        Ok(qbench)
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use sqlx::{query_as, AnyConnection};
+use tokio::time::Duration;
+
+use crate::{dialect, util};
+
+/// A statement of the workload of a database, as tracked by `pg_stat_statements`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedStatement {
+    /// The normalized text of the statement, its constants replaced by `$1`, `$2`...
+    pub query: String,
+    /// How many times the statement was executed.
+    pub calls: u64,
+    /// The average execution time of the statement.
+    pub mean: Duration,
+}
+
+impl CapturedStatement {
+    /// Returns true when the normalized statement has parameters, which need values before it
+    /// can be benchmarked.
+    pub fn has_parameters(&self) -> bool {
+        Regex::new(r"\$\d+").unwrap().is_match(&self.query)
+    }
+}
+
+/// Reads the statements of the current database that took the most time in total from
+/// `pg_stat_statements`, the most expensive first.
+///
+/// # Arguments
+///
+/// * `conn` - The connection to the database.
+/// * `top` - The maximum number of statements read.
+pub async fn top_statements(
+    conn: &mut AnyConnection,
+    top: usize,
+) -> Result<Vec<CapturedStatement>> {
+    let statement = dialect::top_statements(conn.kind(), top)
+        .ok_or_else(|| anyhow!("Capturing statements requires Postgres and pg_stat_statements"))?;
+    let rows: Vec<(String, i64, f64)> = query_as(&statement)
+        .fetch_all(&mut *conn)
+        .await
+        .context("Error reading pg_stat_statements, is the extension installed?")?;
+    Ok(rows
+        .into_iter()
+        .map(|(query, calls, mean_ms)| CapturedStatement {
+            query: query.trim().to_string(),
+            calls: calls.max(0) as u64,
+            mean: Duration::from_secs_f64(mean_ms.max(0.0) / 1000.0),
+        })
+        .collect())
+}
+
+/// Renders a benchmark configuration in TOML with one benchmark per statement, each with a
+/// single `captured` revision to compare rewrites of the statement against.
+///
+/// Statements with parameters are disabled until their parameters are replaced with values.
+///
+/// # Arguments
+///
+/// * `statements` - The captured statements, in the order of the benchmarks.
+/// * `source` - Where the statements were captured, e.g. `pg_stat_statements of <url>`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use qbench::capture::{render_config, CapturedStatement};
+/// use qbench::QueryBenches;
+///
+/// let statements = vec![CapturedStatement {
+///     query: "SELECT * FROM orders WHERE id = $1".to_string(),
+///     calls: 120,
+///     mean: Duration::from_millis(3),
+/// }];
+/// let config = render_config(&statements, "pg_stat_statements of postgres://localhost/shop");
+/// let benches: QueryBenches = toml::from_str(&config)?;
+/// assert_eq!(benches.queries.len(), 1);
+/// # Ok::<(), toml::de::Error>(())
+/// ```
+pub fn render_config(statements: &[CapturedStatement], source: &str) -> String {
+    let mut config = format!(
+        "# Captured from {} by qbench, the statements that took the most time first.\n\
+        # Add revisions rewriting a statement to compare them against it.\n",
+        source
+    );
+    for (i, statement) in statements.iter().enumerate() {
+        let description = format!(
+            "{} call{}, {} on average",
+            statement.calls,
+            if statement.calls == 1 { "" } else { "s" },
+            util::format_duration_pretty(&statement.mean)
+        );
+        let _ = write!(
+            config,
+            "\n[[queries]]\nname = \"statement_{}\"\ndescription = {}\n",
+            i + 1,
+            toml_string(&description)
+        );
+        if statement.has_parameters() {
+            config.push_str(
+                "enabled = false\n\
+                disabled_reason = \"Replace the $1, $2... parameters of the query with values\"\n",
+            );
+        }
+        let _ = write!(
+            config,
+            "\n[[queries.revisions]]\nname = \"captured\"\nquery = {}\n",
+            toml_string(&statement.query)
+        );
+    }
+    config
+}
+
+/// Writes the configuration rendered by [`render_config`] to a file.
+///
+/// # Arguments
+///
+/// * `path` - The file the configuration is written to.
+/// * `config` - The configuration.
+/// * `force` - Whether an existing file may be overwritten.
+pub fn write_config(path: &Path, config: &str, force: bool) -> Result<()> {
+    // Refuse to clobber user files unless explicitly asked to
+    if !force && path.exists() {
+        return Err(anyhow!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        ));
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, config)?;
+    Ok(())
+}
+
+/// Quotes a string as a TOML value, multi-line statements included.
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
@@ -0,0 +1,245 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use console::Term;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::args::ChartFormat;
+use crate::bench::QBench;
+use crate::{util, QueryBenchResult};
+
+/// Size of the charts, in pixels.
+const SIZE: (u32, u32) = (900, 480);
+
+/// Number of bins of the latency distributions.
+const BINS: usize = 30;
+
+/// Colors of the revisions, in order.
+const COLORS: [RGBColor; 7] = [
+    RGBColor(0x29, 0x80, 0xb9),
+    RGBColor(0xe6, 0x7e, 0x22),
+    RGBColor(0x8e, 0x44, 0xad),
+    RGBColor(0x16, 0xa0, 0x85),
+    RGBColor(0xc0, 0x39, 0x2b),
+    RGBColor(0x7f, 0x8c, 0x8d),
+    RGBColor(0xd3, 0x54, 0x00),
+];
+
+/// The charts drawn for each benchmark.
+#[derive(Clone, Copy)]
+enum Chart {
+    /// The average latency of each revision, with its p50 to p99 range.
+    Comparison,
+    /// The share of iterations of each revision by latency.
+    Distribution,
+}
+
+/// The latencies of a revision, in milliseconds.
+struct Latencies {
+    name: String,
+    sorted: Vec<f64>,
+    avg: f64,
+}
+
+impl Latencies {
+    fn percentile(&self, percentile: f64) -> f64 {
+        let rank = (percentile / 100.0 * self.sorted.len() as f64).ceil() as usize;
+        self.sorted[rank.clamp(1, self.sorted.len()) - 1]
+    }
+}
+
+/// Renders charts of the results, for embedding into wikis and slide decks.
+///
+/// Every benchmark with a revision that completed an iteration gets a `<benchmark>/comparison`
+/// chart of the average latency of its revisions, and a `<benchmark>/distribution` chart of
+/// their latencies, under the directory of `--out-file`, as SVG or PNG images according to
+/// `--chart-format`.
+///
+/// # Arguments
+///
+/// * `term` - Terminal interface for displaying progress and status messages.
+/// * `qbench` - Query benchmark configuration.
+/// * `bench_res` - Query benchmark results to be exported.
+pub fn export(term: &Term, qbench: &QBench, bench_res: &[QueryBenchResult]) -> Result<()> {
+    term.write_line("Rendering charts...")?;
+    let root = util::out_file(qbench)?;
+    let format = qbench.args.chart_format;
+    let ext = match format {
+        ChartFormat::Svg => "svg",
+        ChartFormat::Png => "png",
+    };
+
+    for bench in bench_res {
+        let revisions: Vec<Latencies> = bench
+            .results
+            .iter()
+            .filter(|r| !r.durations.is_empty())
+            .map(|r| {
+                let mut sorted: Vec<f64> =
+                    r.durations.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+                sorted.sort_by(f64::total_cmp);
+                Latencies {
+                    name: r.revision_name.clone(),
+                    sorted,
+                    avg: r.avg_query_duration.as_secs_f64() * 1000.0,
+                }
+            })
+            .collect();
+        if revisions.is_empty() {
+            continue;
+        }
+
+        let dir = Path::new(&root).join(util::directory_name(&bench.name));
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Error creating directory {}", dir.display()))?;
+        for (chart, name) in [
+            (Chart::Comparison, "comparison"),
+            (Chart::Distribution, "distribution"),
+        ] {
+            let path = dir.join(format!("{}.{}", name, ext));
+            let error = |e: &dyn Display| anyhow!("Error drawing {}: {}", path.display(), e);
+            match format {
+                ChartFormat::Svg => {
+                    let area = SVGBackend::new(&path, SIZE).into_drawing_area();
+                    draw(&area, chart, &bench.name, &revisions).map_err(|e| error(&e))?
+                }
+                ChartFormat::Png => {
+                    let area = BitMapBackend::new(&path, SIZE).into_drawing_area();
+                    draw(&area, chart, &bench.name, &revisions).map_err(|e| error(&e))?
+                }
+            }
+        }
+    }
+
+    term.clear_last_lines(1)?;
+    term.write_line(&format!("Charts rendered in {}.", root))?;
+    Ok(())
+}
+
+/// Draws a chart of the latencies of the revisions of a benchmark.
+fn draw<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    chart: Chart,
+    bench: &str,
+    revisions: &[Latencies],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    area.fill(&WHITE)?;
+    match chart {
+        Chart::Comparison => draw_comparison(area, bench, revisions)?,
+        Chart::Distribution => draw_distribution(area, bench, revisions)?,
+    }
+    area.present()
+}
+
+/// Draws a bar per revision, its average latency, with a whisker from its p50 to its p99.
+fn draw_comparison<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    bench: &str,
+    revisions: &[Latencies],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let max = revisions
+        .iter()
+        .map(|r| r.percentile(99.0).max(r.avg))
+        .fold(0.001, f64::max);
+    let mut chart = ChartBuilder::on(area)
+        .caption(format!("{}: average latency", bench), ("sans-serif", 22))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d((0..revisions.len() - 1).into_segmented(), 0.0..max * 1.1)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .y_desc("ms")
+        .x_labels(revisions.len())
+        .x_label_formatter(&|segment| match segment {
+            SegmentValue::CenterOf(i) => revisions[*i].name.clone(),
+            _ => String::new(),
+        })
+        .draw()?;
+
+    chart.draw_series(revisions.iter().enumerate().map(|(i, r)| {
+        let color = COLORS[i % COLORS.len()];
+        let mut bar = Rectangle::new(
+            [(SegmentValue::Exact(i), 0.0), (SegmentValue::Exact(i + 1), r.avg)],
+            color.filled(),
+        );
+        bar.set_margin(0, 0, 20, 20);
+        bar
+    }))?;
+    chart.draw_series(revisions.iter().enumerate().map(|(i, r)| {
+        PathElement::new(
+            vec![
+                (SegmentValue::CenterOf(i), r.percentile(50.0)),
+                (SegmentValue::CenterOf(i), r.percentile(99.0)),
+            ],
+            BLACK.stroke_width(2),
+        )
+    }))?;
+    Ok(())
+}
+
+/// Draws the share of iterations of each revision by latency, over bins shared by all of them.
+fn draw_distribution<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    bench: &str,
+    revisions: &[Latencies],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let min = revisions.iter().map(|r| r.sorted[0]).fold(f64::INFINITY, f64::min);
+    let max = revisions
+        .iter()
+        .map(|r| r.sorted[r.sorted.len() - 1])
+        .fold(0.0, f64::max);
+    let width = match (max - min) / BINS as f64 {
+        w if w > 0.0 => w,
+        _ => 0.001,
+    };
+    let shares: Vec<Vec<(f64, f64)>> = revisions
+        .iter()
+        .map(|r| {
+            let mut bins = [0usize; BINS];
+            for v in &r.sorted {
+                bins[(((v - min) / width) as usize).min(BINS - 1)] += 1;
+            }
+            bins.iter()
+                .enumerate()
+                .map(|(bin, count)| {
+                    let center = min + (bin as f64 + 0.5) * width;
+                    (center, *count as f64 / r.sorted.len() as f64 * 100.0)
+                })
+                .collect()
+        })
+        .collect();
+    let top = shares.iter().flatten().map(|(_, share)| *share).fold(1.0, f64::max);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(format!("{}: latency distribution", bench), ("sans-serif", 22))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(min..min + width * BINS as f64, 0.0..top * 1.1)?;
+    chart
+        .configure_mesh()
+        .x_desc("ms")
+        .y_desc("% of iterations")
+        .draw()?;
+
+    for (i, (r, points)) in revisions.iter().zip(shares).enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        chart
+            .draw_series(LineSeries::new(points, color.stroke_width(2)))?
+            .label(r.name.as_str())
+            .legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+            });
+    }
+    chart
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.8))
+        .draw()?;
+    Ok(())
+}
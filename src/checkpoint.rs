@@ -0,0 +1,84 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::{QueryBenchResult, RevisionStatus};
+
+/// Writes completed benchmark results to a checkpoint file as they finish, one JSON document
+/// per line, so an interrupted run can be resumed with [`load`].
+pub struct CheckpointWriter {
+    file: File,
+}
+
+impl CheckpointWriter {
+    /// Creates (or truncates) the checkpoint file and writes the given results into it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the checkpoint file.
+    /// * `completed` - Results already completed, e.g. the ones loaded when resuming.
+    pub fn create(path: &Path, completed: &[QueryBenchResult]) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Error creating checkpoint file {}", path.display()))?;
+        let mut writer = Self { file };
+        for result in completed {
+            writer.append(result)?;
+        }
+        Ok(writer)
+    }
+
+    /// Appends a completed benchmark result to the checkpoint file.
+    ///
+    /// Results of benchmarks that were interrupted are not written, so they run again on resume.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The completed benchmark result.
+    pub fn append(&mut self, result: &QueryBenchResult) -> Result<()> {
+        if !is_complete(result) {
+            return Ok(());
+        }
+        writeln!(self.file, "{}", serde_json::to_string(result)?)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Loads the benchmark results written to a checkpoint file by [`CheckpointWriter`].
+///
+/// A truncated last line, left behind when the process died mid-write, is ignored.
+///
+/// # Arguments
+///
+/// * `path` - Path of the checkpoint file.
+pub fn load(path: &Path) -> Result<Vec<QueryBenchResult>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Error reading checkpoint file {}", path.display()))?;
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    let mut results = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        match serde_json::from_str::<QueryBenchResult>(line) {
+            Ok(result) => results.push(result),
+            Err(_) if index == lines.len() - 1 => break,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context(format!(
+                    "Invalid checkpoint entry on line {} of {}",
+                    index + 1,
+                    path.display()
+                )))
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Checks whether all revisions of the benchmark ran to an end, i.e. none was interrupted.
+fn is_complete(result: &QueryBenchResult) -> bool {
+    result
+        .results
+        .iter()
+        .all(|rev| rev.status != RevisionStatus::Interrupted)
+}
@@ -0,0 +1,118 @@
+use sha2::{Digest, Sha256};
+use sqlx::any::AnyRow;
+use sqlx::{Any, Column, Decode, Row, Type};
+
+/// Checksum of the rows returned by a query, independent of the order they are returned in so
+/// plan changes reordering rows without an `ORDER BY` don't change it.
+///
+/// Rows are hashed one at a time as they are fetched, using constant memory whatever the size
+/// of the result set.
+#[derive(Debug, Clone, Default)]
+pub struct ResultChecksum {
+    /// Wrapping sum of the row digests, split in 64 bit lanes.
+    lanes: [u64; 4],
+    rows: u64,
+}
+
+impl ResultChecksum {
+    /// Adds a row to the checksum.
+    ///
+    /// Values are hashed by their text representation, so integers of different widths and
+    /// the same text in different string types produce the same checksum. Types sqlx can't
+    /// decode through the `Any` driver are hashed as the value sent by the database, which
+    /// differs between the prepared and text protocols.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - A row returned by the query.
+    pub fn add(&mut self, row: &AnyRow) -> Result<(), sqlx::Error> {
+        let mut hasher = Sha256::new();
+        for index in 0..row.len() {
+            match value_bytes(row, index)? {
+                Some(bytes) => {
+                    hasher.update([1]);
+                    hasher.update((bytes.len() as u64).to_le_bytes());
+                    hasher.update(bytes);
+                }
+                None => hasher.update([0]),
+            }
+        }
+        for (lane, chunk) in self.lanes.iter_mut().zip(hasher.finalize().chunks(8)) {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(chunk);
+            *lane = lane.wrapping_add(u64::from_le_bytes(bytes));
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Returns the checksum of the rows added so far as a hex string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use qbench::checksum::ResultChecksum;
+    ///
+    /// let checksum = ResultChecksum::default().finish();
+    /// assert_eq!(checksum.len(), 64);
+    /// ```
+    pub fn finish(&self) -> String {
+        let mut hasher = Sha256::new();
+        for lane in self.lanes {
+            hasher.update(lane.to_le_bytes());
+        }
+        hasher.update(self.rows.to_le_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Returns the bytes a value of a row is hashed by, `None` for `NULL`.
+///
+/// # Arguments
+///
+/// * `row` - The row the value belongs to.
+/// * `index` - Index of the column.
+fn value_bytes(row: &AnyRow, index: usize) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let text = decode_text::<bool>(row, index)
+        .or_else(|| decode_text::<i16>(row, index))
+        .or_else(|| decode_text::<i32>(row, index))
+        .or_else(|| decode_text::<i64>(row, index))
+        .or_else(|| decode_text::<f32>(row, index))
+        .or_else(|| decode_text::<f64>(row, index))
+        .or_else(|| decode_text::<String>(row, index));
+    if let Some(text) = text {
+        return Ok(text.map(String::into_bytes));
+    }
+
+    // Other types (numerics, dates, uuids...) are sent as text by the simple query protocol,
+    // and in a binary format that may not be valid UTF-8 by the prepared one
+    match row.try_get_unchecked::<Option<String>, _>(index) {
+        Ok(text) => Ok(text.map(String::into_bytes)),
+        Err(_) => {
+            let column = &row.columns()[index];
+            Err(sqlx::Error::Decode(
+                format!(
+                    "cannot checksum column {} of type {}, cast it to text or use the text protocol",
+                    column.name(),
+                    column.type_info()
+                )
+                .into(),
+            ))
+        }
+    }
+}
+
+/// Decodes a value as `T` and formats it, or returns `None` when the column is not of type `T`.
+///
+/// # Arguments
+///
+/// * `row` - The row the value belongs to.
+/// * `index` - Index of the column.
+fn decode_text<T>(row: &AnyRow, index: usize) -> Option<Option<String>>
+where
+    T: for<'r> Decode<'r, Any> + Type<Any> + ToString,
+{
+    row.try_get::<Option<T>, _>(index)
+        .ok()
+        .map(|value| value.map(|value| value.to_string()))
+}
@@ -0,0 +1,117 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tabled::builder::Builder;
+use tabled::Table;
+use tokio_util::sync::CancellationToken;
+
+use crate::args::Args;
+use crate::bench::QBench;
+use crate::{util, QueryBenchResult};
+
+/// The results of the suite on one of the databases it ran against.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TargetResults {
+    /// The database, described by its version, e.g. `PostgreSQL 16.1`.
+    pub target: String,
+    pub results: Vec<QueryBenchResult>,
+}
+
+/// Runs the suite against another database, configured like the first one but for its URL.
+///
+/// # Arguments
+///
+/// * `args` - The configuration of the first run.
+/// * `url` - Connection URL of the database.
+/// * `cancel` - Token interrupting the run, shared with the first one.
+pub async fn run_target(
+    args: &Args,
+    url: &str,
+    cancel: CancellationToken,
+) -> Result<TargetResults> {
+    let mut args = args.clone();
+    args.url = url.to_string();
+    // The database has no replica of its own, the benchmarks reading from one read from it
+    if args.replica_url.is_some() {
+        args.replica_url = Some(url.to_string());
+    }
+    let mut qbench = QBench::new(args, true).await?;
+    qbench.set_cancellation_token(cancel);
+    let version = qbench.preflight().await?;
+    let results = qbench.run_bench().await?;
+    Ok(TargetResults {
+        target: short_version(&version),
+        results,
+    })
+}
+
+/// Shortens the version reported by a database to its name and number, e.g. `PostgreSQL 16.1`
+/// out of `PostgreSQL 16.1 on x86_64-pc-linux-gnu, compiled by gcc ...`.
+pub fn short_version(version: &str) -> String {
+    let first_line = version.lines().next().unwrap_or_default();
+    let short = first_line.split(" on ").next().unwrap_or(first_line);
+    short.split(',').next().unwrap_or(short).trim().to_string()
+}
+
+/// Lines up the average latency of each revision across the databases, with its difference
+/// relative to the first database.
+///
+/// Revisions are listed in the order of the first database they ran on, a `-` standing for
+/// databases on which they didn't run or completed no iteration.
+///
+/// # Arguments
+///
+/// * `targets` - The results of each database, the first one being the reference.
+pub fn comparison_table(targets: &[TargetResults]) -> Table {
+    let mut rows: Vec<(&str, &str)> = vec![];
+    for target in targets {
+        for bench in &target.results {
+            for revision in &bench.results {
+                let row = (bench.name.as_str(), revision.revision_name.as_str());
+                if !rows.contains(&row) {
+                    rows.push(row);
+                }
+            }
+        }
+    }
+
+    let mut builder = Builder::default();
+    let header = ["Benchmark", "Revision"]
+        .into_iter()
+        .map(String::from)
+        .chain(targets.iter().map(|target| target.target.clone()));
+    builder.set_header(header);
+
+    for (bench, revision) in rows {
+        let averages: Vec<_> = targets
+            .iter()
+            .map(|target| {
+                target
+                    .results
+                    .iter()
+                    .find(|b| b.name == bench)
+                    .and_then(|b| b.results.iter().find(|r| r.revision_name == revision))
+                    .filter(|r| !r.durations.is_empty())
+                    .map(|r| r.avg_query_duration)
+            })
+            .collect();
+        let mut record = vec![bench.to_string(), revision.to_string()];
+        for (index, average) in averages.iter().enumerate() {
+            let cell = match (average, averages[0]) {
+                (None, _) => "-".to_string(),
+                (Some(average), Some(reference)) if index > 0 && !reference.is_zero() => {
+                    let change = (average.as_secs_f64() - reference.as_secs_f64())
+                        / reference.as_secs_f64()
+                        * 100.0;
+                    format!("{} ({:+.1}%)", util::format_duration_pretty(average), change)
+                }
+                (Some(average), _) => util::format_duration_pretty(average),
+            };
+            record.push(cell);
+        }
+        builder.push_record(record);
+    }
+
+    let mut table = builder.build();
+    util::style_table(&mut table);
+    table
+}
@@ -0,0 +1,184 @@
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use sqlx::any::AnyConnectOptions;
+use sqlx::{AnyConnection, Connection};
+use tokio::process::Command;
+use tokio::time::{self, Instant};
+use tracing::{debug, info};
+
+/// Password of the database user in the container.
+const PASSWORD: &str = "qbench";
+
+/// How long the database of a container may take to accept connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A disposable database running in a Docker container, removed when dropped.
+pub struct Container {
+    id: String,
+    /// Connection URL of the database, on a port of this host.
+    pub url: String,
+}
+
+/// Databases the official images of which can be started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engine {
+    Postgres,
+    MySql,
+}
+
+impl Engine {
+    /// Recognizes the engine of an image by its name, e.g. `postgres:16` or `mariadb:11`.
+    fn of_image(image: &str) -> Result<Self> {
+        // Ignore the registry and tag, e.g. `docker.io/library/postgres:16`
+        let name = image.rsplit('/').next().unwrap_or(image);
+        let name = name.split([':', '@']).next().unwrap_or(name).to_lowercase();
+        if ["postgres", "postgis", "timescaledb"].iter().any(|n| name.contains(n)) {
+            Ok(Engine::Postgres)
+        } else if ["mysql", "mariadb"].iter().any(|n| name.contains(n)) {
+            Ok(Engine::MySql)
+        } else {
+            bail!("Unsupported image {}, expected a Postgres, MySQL or MariaDB image", image)
+        }
+    }
+
+    fn port(&self) -> u16 {
+        match self {
+            Engine::Postgres => 5432,
+            Engine::MySql => 3306,
+        }
+    }
+
+    /// Environment variables initializing the database of the image.
+    fn env(&self) -> Vec<String> {
+        match self {
+            Engine::Postgres => vec![format!("POSTGRES_PASSWORD={}", PASSWORD)],
+            Engine::MySql => vec![
+                format!("MYSQL_ROOT_PASSWORD={}", PASSWORD),
+                "MYSQL_DATABASE=qbench".to_string(),
+            ],
+        }
+    }
+
+    /// Connection URL of the database initialized by [`Engine::env`].
+    fn url(&self, port: u16) -> String {
+        match self {
+            Engine::Postgres => {
+                format!("postgres://postgres:{}@127.0.0.1:{}/postgres", PASSWORD, port)
+            }
+            Engine::MySql => format!("mysql://root:{}@127.0.0.1:{}/qbench", PASSWORD, port),
+        }
+    }
+}
+
+impl Container {
+    /// Starts a container of the image and waits for its database to accept connections.
+    ///
+    /// The database port is published on a free port of the loopback interface. The container
+    /// is removed if the database doesn't come up.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The image, e.g. `postgres:16`.
+    pub async fn start(image: &str) -> Result<Self> {
+        let engine = Engine::of_image(image)?;
+        let mut run = Command::new("docker");
+        run.args(["run", "--detach", "--rm"])
+            .args(["--publish", &format!("127.0.0.1::{}", engine.port())]);
+        for env in engine.env() {
+            run.args(["--env", &env]);
+        }
+        let id = docker(run.arg(image))
+            .await
+            .with_context(|| format!("Error starting a container of {}", image))?;
+        info!(image, id = %id, "container started");
+
+        // Remove the container from here on if anything fails
+        let mut container = Container { id, url: String::new() };
+        let port = docker(Command::new("docker").args([
+            "port",
+            &container.id,
+            &format!("{}/tcp", engine.port()),
+        ]))
+        .await?;
+        // One line per published address, e.g. `127.0.0.1:49153`
+        let port: u16 = port
+            .lines()
+            .next()
+            .and_then(|line| line.rsplit(':').next())
+            .and_then(|port| port.trim().parse().ok())
+            .ok_or_else(|| anyhow!("Unexpected output of docker port: {}", port))?;
+        container.url = engine.url(port);
+        container.wait_ready().await?;
+        Ok(container)
+    }
+
+    /// Returns the identifier of the container.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Waits for the database to accept connections, images initializing it before listening.
+    async fn wait_ready(&self) -> Result<()> {
+        let options = AnyConnectOptions::from_str(&self.url)?;
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            match AnyConnection::connect_with(&options).await {
+                Ok(conn) => {
+                    let _ = conn.close().await;
+                    info!(id = %self.id, "container ready");
+                    return Ok(());
+                }
+                Err(e) if Instant::now() < deadline => {
+                    debug!(error = %e, "container not ready");
+                    time::sleep(Duration::from_millis(500)).await;
+                }
+                Err(e) => {
+                    return Err(anyhow::Error::new(e).context(format!(
+                        "The database of container {} didn't accept connections within {}",
+                        self.id,
+                        humantime::format_duration(STARTUP_TIMEOUT)
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Container {
+    fn drop(&mut self) {
+        // Blocking, but only once the run is over
+        remove(&self.id);
+    }
+}
+
+/// Removes a container, for when the process exits without dropping its [`Container`].
+///
+/// # Arguments
+///
+/// * `id` - Identifier of the container, see [`Container::id`].
+pub fn remove(id: &str) {
+    let removed = std::process::Command::new("docker")
+        .args(["rm", "--force", id])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if !matches!(removed, Ok(status) if status.success()) {
+        eprintln!("Could not remove container {}, remove it with `docker rm -f {}`", id, id);
+    }
+}
+
+/// Runs a docker command and returns its standard output, trimmed.
+async fn docker(command: &mut Command) -> Result<String> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Error running docker")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
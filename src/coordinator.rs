@@ -0,0 +1,524 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use futures::future::join_all;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::time;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::args::Shard;
+use crate::bench::QBench;
+use crate::server::{RunRequest, RunStatus, RunSummary};
+use crate::util::{self, average_duration};
+use crate::{
+    LatencySlo, PlanSample, QueryBench, QueryBenchResult, QueryRevisionResult, RevisionStatus,
+};
+
+/// How often the workers are asked for the status of their share.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How many polls in a row may fail before the run is given up on and the shares cancelled.
+const MAX_POLL_FAILURES: usize = 10;
+
+/// Runs the suite on `qbench serve` workers, each taking an equal share of the iterations of
+/// every revision, and merges the samples they return into one set of results.
+///
+/// The workers start their share at the same time, so together they put the load of the whole
+/// suite on the database. Interrupting the run through the token of `qbench` cancels the shares
+/// and merges what the workers completed so far.
+///
+/// # Arguments
+///
+/// * `qbench` - The instance configured by the command line, its benchmark, revision and tag
+///   filters and iteration count are passed on to the workers.
+/// * `workers` - Base URLs of the workers.
+pub async fn run(qbench: &QBench, workers: &[Url]) -> Result<Vec<QueryBenchResult>> {
+    let client = reqwest::Client::new();
+    let cancel = qbench.cancellation_token();
+
+    let starts = workers.iter().enumerate().map(|(index, worker)| {
+        let request = RunRequest {
+            bench: qbench.args.bench.clone(),
+            suites: qbench.args.suites.clone(),
+            revision: qbench.args.revision.clone(),
+            tags: qbench.args.tags.clone(),
+            iterations: Some(qbench.args.iterations),
+            shard: Some(Shard {
+                index: index + 1,
+                count: workers.len(),
+            }),
+        };
+        let client = &client;
+        async move {
+            post::<_, RunSummary>(client, worker, "runs", &request)
+                .await
+                .map(|summary| summary.id)
+        }
+    });
+    let started = join_all(starts).await;
+
+    // Don't leave the other shares running when one of them couldn't start
+    let mut ids = vec![];
+    for (worker, id) in workers.iter().zip(&started) {
+        match id {
+            Ok(id) => ids.push(*id),
+            Err(e) => {
+                cancel_all(&client, workers, &started).await;
+                return Err(anyhow!("{:#}", e)
+                    .context(format!("Error starting a run on worker {}", worker)));
+            }
+        }
+    }
+    info!(workers = workers.len(), "shares started");
+
+    let mut completed = vec![0; workers.len()];
+    let mut cancelled = false;
+    let mut failures = 0;
+    let summaries = loop {
+        tokio::select! {
+            _ = time::sleep(POLL_INTERVAL) => {}
+            _ = cancel.cancelled(), if !cancelled => {
+                cancelled = true;
+                cancel_all(&client, workers, &started).await;
+            }
+        }
+
+        let polls = workers.iter().zip(&ids).map(|(worker, id)| {
+            get::<RunSummary>(&client, worker, format!("runs/{}", id))
+        });
+        let summaries = match join_all(polls).await.into_iter().collect::<Result<Vec<_>>>() {
+            Ok(summaries) => summaries,
+            // A worker briefly out of reach is asked again on the next poll
+            Err(e) if failures < MAX_POLL_FAILURES => {
+                failures += 1;
+                warn!(error = %format!("{:#}", e), failures, "could not poll the workers");
+                continue;
+            }
+            Err(e) => {
+                cancel_all(&client, workers, &started).await;
+                return Err(e.context("Error polling the workers"));
+            }
+        };
+        failures = 0;
+        for ((worker, summary), completed) in workers.iter().zip(&summaries).zip(&mut completed) {
+            if summary.completed != *completed {
+                *completed = summary.completed;
+                info!(worker = %worker, completed = summary.completed, "worker progress");
+            }
+        }
+        if summaries.iter().all(|s| s.status != RunStatus::Running) {
+            break summaries;
+        }
+    };
+
+    if let Some((worker, summary)) = workers
+        .iter()
+        .zip(&summaries)
+        .find(|(_, summary)| summary.status == RunStatus::Failed)
+    {
+        cancel_all(&client, workers, &started).await;
+        return Err(anyhow!(
+            "Worker {} failed: {}",
+            worker,
+            summary.error.as_deref().unwrap_or("unknown error")
+        ));
+    }
+
+    let fetches = workers.iter().zip(&ids).map(|(worker, id)| {
+        get::<Vec<QueryBenchResult>>(&client, worker, format!("runs/{}/results", id))
+    });
+    let shares = join_all(fetches)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+    Ok(merge_results(shares, &qbench.latency_slos().await?))
+}
+
+/// Merges the results of the shares of a run, benchmarks and revisions in the order the first
+/// share completed them.
+///
+/// The samples of each revision are pooled, so the statistics derived from them cover every
+/// iteration of the run. A revision that failed on a share keeps the status and message of the
+/// first share it failed on. The others are checked against their latency objectives again,
+/// on the pooled samples. The confidence intervals and noise estimated on a share are dropped,
+/// to be estimated again from the pooled samples, see [`util::estimate_confidence`] and
+/// [`crate::history::estimate_noise`].
+///
+/// # Arguments
+///
+/// * `shares` - The results of each share.
+/// * `slos` - The latency objectives of each benchmark and revision, see [`latency_slos`].
+pub fn merge_results(
+    shares: Vec<Vec<QueryBenchResult>>,
+    slos: &HashMap<(String, String), LatencySlo>,
+) -> Vec<QueryBenchResult> {
+    let mut merged: Vec<QueryBenchResult> = vec![];
+    for share in shares {
+        for bench in share {
+            let Some(existing) = merged.iter_mut().find(|b| b.name == bench.name) else {
+                merged.push(bench);
+                continue;
+            };
+            for revision in bench.results {
+                match existing
+                    .results
+                    .iter_mut()
+                    .find(|r| r.revision_name == revision.revision_name)
+                {
+                    Some(into) => merge_revision(into, revision),
+                    None => existing.results.push(revision),
+                }
+            }
+        }
+    }
+
+    for bench in &mut merged {
+        for revision in &mut bench.results {
+            revision.confidence = None;
+            revision.noise = None;
+            if !matches!(revision.status, RevisionStatus::Success | RevisionStatus::SloViolated) {
+                continue;
+            }
+            let key = (bench.name.clone(), revision.revision_name.clone());
+            let slo = slos.get(&key).copied().unwrap_or_default();
+            let violations = slo.violations(&revision.durations);
+            match violations.is_empty() {
+                true => {
+                    revision.status = RevisionStatus::Success;
+                    revision.message = None;
+                }
+                false => revision.mark_slo_violated(violations),
+            }
+        }
+    }
+    merged
+}
+
+/// Returns the latency objectives of the revisions of the given benchmarks, including the ones
+/// they inherit from their benchmark, by benchmark and revision name.
+///
+/// # Arguments
+///
+/// * `benches` - The benchmarks.
+pub fn latency_slos(benches: &[QueryBench]) -> HashMap<(String, String), LatencySlo> {
+    benches
+        .iter()
+        .flat_map(|bench| {
+            bench.revisions.iter().map(move |revision| {
+                let key = (bench.name.clone(), revision.name.clone());
+                (key, bench.effective_revision(revision).slo())
+            })
+        })
+        .collect()
+}
+
+/// Adds the samples of a share of a revision to the results of the others.
+///
+/// # Arguments
+///
+/// * `into` - The results of the shares merged so far.
+/// * `share` - The results of one more share.
+fn merge_revision(into: &mut QueryRevisionResult, share: QueryRevisionResult) {
+    // Latency objectives are checked again once the samples are pooled
+    let failed = |status| !matches!(status, RevisionStatus::Success | RevisionStatus::SloViolated);
+    if !failed(into.status) && failed(share.status) {
+        into.status = share.status;
+        into.message = share.message;
+    }
+
+    // Weigh the hook overhead, and the average of each round, of each share by the iterations
+    // it measured
+    let (before, added) = (into.durations.len(), share.durations.len());
+    if before + added > 0 {
+        let weigh = |into: Duration, share: Duration| {
+            (into * before as u32 + share * added as u32) / (before + added) as u32
+        };
+        into.avg_hook_duration = weigh(into.avg_hook_duration, share.avg_hook_duration);
+        match into.round_averages.len() == share.round_averages.len() {
+            true => {
+                for (into, share) in into.round_averages.iter_mut().zip(share.round_averages) {
+                    *into = weigh(*into, share);
+                }
+            }
+            false => into.round_averages.clear(),
+        }
+    }
+
+    // The plans of the share were explained before its own iterations, which follow these
+    let iterations = before + into.discarded_durations.len();
+    into.plans.extend(share.plans.into_iter().map(|plan| PlanSample {
+        iteration: plan.iteration + iterations,
+        ..plan
+    }));
+    into.durations.extend(share.durations);
+    into.timestamps.extend(share.timestamps);
+    into.cold_durations.extend(share.cold_durations);
+    into.discarded_durations.extend(share.discarded_durations);
+    into.avg_query_duration = average_duration(&into.durations);
+    into.retries += share.retries;
+    into.rows = into.rows.or(share.rows);
+    into.fetch_chunks = into.fetch_chunks.or(share.fetch_chunks);
+    into.checksum = into.checksum.take().or(share.checksum);
+    into.acquire_duration = into.acquire_duration.max(share.acquire_duration);
+    into.pre_script_duration = into.pre_script_duration.max(share.pre_script_duration);
+    into.post_script_duration = into.post_script_duration.max(share.post_script_duration);
+    into.iterations_duration = into.iterations_duration.max(share.iterations_duration);
+    match (&mut into.aborts, share.aborts) {
+        (Some(aborts), Some(share_aborts)) => aborts.add(&share_aborts),
+        (aborts, share_aborts) => *aborts = aborts.take().or(share_aborts),
+    }
+    match (&mut into.io, share.io) {
+        (Some(io), Some(share_io)) => io.add(&share_io),
+        (io, share_io) => *io = io.or(share_io),
+    }
+    for wait in share.waits {
+        match into.waits.iter_mut().find(|w| w.is_same(&wait)) {
+            Some(w) => w.duration += wait.duration,
+            None => into.waits.push(wait),
+        }
+    }
+    into.waits.sort_by_key(|w| Reverse(w.duration));
+}
+
+/// Cancels the shares that were started, ignoring the workers that can't be reached.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client.
+/// * `workers` - Base URLs of the workers.
+/// * `started` - The id of the run of each worker, if it started.
+async fn cancel_all(client: &reqwest::Client, workers: &[Url], started: &[Result<u64>]) {
+    let cancels = workers.iter().zip(started).filter_map(|(worker, id)| {
+        let id = id.as_ref().ok()?;
+        Some(post::<_, RunSummary>(client, worker, format!("runs/{}/cancel", id), &()))
+    });
+    join_all(cancels).await;
+}
+
+/// Sends a GET request to a worker, returning the JSON body of the response.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client.
+/// * `worker` - Base URL of the worker.
+/// * `path` - Path of the endpoint, relative to the base URL.
+async fn get<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    worker: &Url,
+    path: impl AsRef<str>,
+) -> Result<T> {
+    let url = endpoint(worker, path.as_ref());
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)
+        .with_context(|| format!("Error requesting {}", util::redact_url(&url)))?;
+    parse(response, &url).await
+}
+
+/// Sends a POST request with a JSON body to a worker, returning the JSON body of the response.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client.
+/// * `worker` - Base URL of the worker.
+/// * `path` - Path of the endpoint, relative to the base URL.
+/// * `body` - The body of the request.
+async fn post<B: Serialize, T: DeserializeOwned>(
+    client: &reqwest::Client,
+    worker: &Url,
+    path: impl AsRef<str>,
+    body: &B,
+) -> Result<T> {
+    let url = endpoint(worker, path.as_ref());
+    let response = client
+        .post(&url)
+        .json(body)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)
+        .with_context(|| format!("Error requesting {}", util::redact_url(&url)))?;
+    parse(response, &url).await
+}
+
+/// Parses the JSON body of a response, turning the error responses of the API into errors
+/// carrying their message.
+async fn parse<T: DeserializeOwned>(response: reqwest::Response, url: &str) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        let message = body["error"].as_str().unwrap_or("no details").to_string();
+        return Err(anyhow!("{} returned {}: {}", util::redact_url(url), status, message));
+    }
+    response
+        .json()
+        .await
+        .with_context(|| format!("Invalid response from {}", util::redact_url(url)))
+}
+
+/// Returns the URL of an endpoint of a worker.
+fn endpoint(worker: &Url, path: &str) -> String {
+    format!("{}/{}", worker.as_str().trim_end_matches('/'), path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision(name: &str, millis: &[u64]) -> QueryRevisionResult {
+        let durations: Vec<Duration> = millis.iter().copied().map(Duration::from_millis).collect();
+        QueryRevisionResult {
+            revision_name: name.to_string(),
+            avg_query_duration: average_duration(&durations),
+            durations,
+            ..Default::default()
+        }
+    }
+
+    fn merge(shares: Vec<Vec<QueryBenchResult>>) -> Vec<QueryBenchResult> {
+        merge_results(shares, &HashMap::new())
+    }
+
+    fn bench(name: &str, results: Vec<QueryRevisionResult>) -> QueryBenchResult {
+        QueryBenchResult {
+            name: name.to_string(),
+            results,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pools_the_samples_of_the_shares() {
+        let mut first = revision("v1", &[10, 20]);
+        first.retries = 1;
+        first.avg_hook_duration = Duration::from_millis(2);
+        let mut second = revision("v1", &[30, 40, 50, 60]);
+        second.retries = 2;
+        second.avg_hook_duration = Duration::from_millis(5);
+
+        let merged = merge(vec![
+            vec![bench("q", vec![first])],
+            vec![bench("q", vec![second])],
+        ]);
+        assert_eq!(merged.len(), 1);
+        let merged = &merged[0].results[0];
+        assert_eq!(merged.durations.len(), 6);
+        assert_eq!(merged.avg_query_duration, Duration::from_millis(35));
+        assert_eq!(merged.retries, 3);
+        // 2 iterations at 2ms and 4 at 5ms
+        assert_eq!(merged.avg_hook_duration, Duration::from_millis(4));
+    }
+
+    #[test]
+    fn keeps_the_order_of_the_first_share() {
+        let merged = merge(vec![
+            vec![
+                bench("b", vec![revision("v1", &[1])]),
+                bench("a", vec![revision("v1", &[1])]),
+            ],
+            vec![
+                bench("a", vec![revision("v1", &[1]), revision("v2", &[1])]),
+                bench("c", vec![revision("v1", &[1])]),
+                bench("b", vec![revision("v1", &[1])]),
+            ],
+        ]);
+        let names: Vec<&str> = merged.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "a", "c"]);
+        let revisions: Vec<&str> = merged[1]
+            .results
+            .iter()
+            .map(|r| r.revision_name.as_str())
+            .collect();
+        assert_eq!(revisions, vec!["v1", "v2"]);
+    }
+
+    #[test]
+    fn keeps_the_first_status_other_than_success() {
+        let mut timed_out = revision("v1", &[10]);
+        timed_out.status = RevisionStatus::TimedOut;
+        timed_out.message = Some("timed out".to_string());
+        let mut failed = revision("v1", &[]);
+        failed.status = RevisionStatus::Failed;
+        failed.message = Some("failed".to_string());
+
+        let merged = merge(vec![
+            vec![bench("q", vec![revision("v1", &[10])])],
+            vec![bench("q", vec![timed_out])],
+            vec![bench("q", vec![failed])],
+        ]);
+        let merged = &merged[0].results[0];
+        assert_eq!(merged.status, RevisionStatus::TimedOut);
+        assert_eq!(merged.message.as_deref(), Some("timed out"));
+        assert_eq!(merged.durations.len(), 2);
+    }
+
+    #[test]
+    fn numbers_the_plans_after_the_iterations_of_the_previous_shares() {
+        let plan = |iteration, hash: &str| PlanSample {
+            iteration,
+            hash: hash.to_string(),
+        };
+        let mut first = revision("v1", &[1, 2, 3]);
+        first.discarded_durations = vec![Duration::from_millis(9)];
+        first.plans = vec![plan(0, "a")];
+        let mut second = revision("v1", &[4, 5]);
+        second.plans = vec![plan(0, "a"), plan(1, "b")];
+
+        let merged = merge(vec![
+            vec![bench("q", vec![first])],
+            vec![bench("q", vec![second])],
+        ]);
+        assert_eq!(
+            merged[0].results[0].plans,
+            vec![plan(0, "a"), plan(4, "a"), plan(5, "b")]
+        );
+    }
+
+    #[test]
+    fn checks_the_pooled_samples_against_the_latency_objectives() {
+        let mut slow = revision("v1", &[30, 30]);
+        slow.mark_slo_violated(vec!["avg of 30ms exceeds 20ms".to_string()]);
+        let slo = LatencySlo {
+            max_avg: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let slos = HashMap::from([(("q".to_string(), "v1".to_string()), slo)]);
+
+        // The second share makes up for the first one
+        let fast = revision("v1", &[5, 5]);
+        let merged = merge_results(
+            vec![vec![bench("q", vec![slow.clone()])], vec![bench("q", vec![fast])]],
+            &slos,
+        );
+        assert_eq!(merged[0].results[0].status, RevisionStatus::Success);
+        assert_eq!(merged[0].results[0].message, None);
+
+        // Shares meeting the objectives on their own may not together
+        let fast = revision("v1", &[19, 25]);
+        let merged = merge_results(
+            vec![vec![bench("q", vec![fast])], vec![bench("q", vec![slow])]],
+            &slos,
+        );
+        assert_eq!(merged[0].results[0].status, RevisionStatus::SloViolated);
+        assert_eq!(merged[0].results[0].message.as_deref(), Some("avg of 26ms exceeds 20ms"));
+    }
+
+    #[test]
+    fn weighs_the_round_averages_of_the_shares() {
+        let mut first = revision("v1", &[10]);
+        first.round_averages = vec![Duration::from_millis(10), Duration::from_millis(20)];
+        let mut second = revision("v1", &[10, 10, 10]);
+        second.round_averages = vec![Duration::from_millis(30), Duration::from_millis(40)];
+
+        let merged = merge(vec![vec![bench("q", vec![first])], vec![bench("q", vec![second])]]);
+        assert_eq!(
+            merged[0].results[0].round_averages,
+            vec![Duration::from_micros(25_000), Duration::from_micros(35_000)]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use console::Term;
+use serde::Serialize;
+
+use crate::bench::QBench;
+use crate::{util, QueryBenchResult};
+
+/// Confidence level of the intervals of the estimates, the one Criterion defaults to.
+const CONFIDENCE_LEVEL: f64 = 0.95;
+
+/// Two-sided z-score of [`CONFIDENCE_LEVEL`].
+const Z_SCORE: f64 = 1.96;
+
+/// Identifies a benchmark in Criterion's `benchmark.json`.
+#[derive(Serialize)]
+struct BenchmarkId {
+    group_id: String,
+    function_id: Option<String>,
+    value_str: Option<String>,
+    throughput: Option<()>,
+    full_id: String,
+    directory_name: String,
+    title: String,
+}
+
+/// The estimates of Criterion's `estimates.json`, in nanoseconds.
+#[derive(Serialize)]
+struct Estimates {
+    mean: Estimate,
+    median: Estimate,
+    median_abs_dev: Estimate,
+    slope: Option<Estimate>,
+    std_dev: Estimate,
+}
+
+#[derive(Serialize)]
+struct Estimate {
+    confidence_interval: ConfidenceInterval,
+    point_estimate: f64,
+    standard_error: f64,
+}
+
+#[derive(Serialize)]
+struct ConfidenceInterval {
+    confidence_level: f64,
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+/// The samples of Criterion's `sample.json`, each iteration being a sample of one iteration.
+#[derive(Serialize)]
+struct Sample {
+    sampling_mode: &'static str,
+    iters: Vec<f64>,
+    times: Vec<f64>,
+}
+
+impl Estimate {
+    /// An estimate with a normal confidence interval around it.
+    fn new(point_estimate: f64, standard_error: f64) -> Self {
+        Estimate {
+            confidence_interval: ConfidenceInterval {
+                confidence_level: CONFIDENCE_LEVEL,
+                lower_bound: point_estimate - Z_SCORE * standard_error,
+                upper_bound: point_estimate + Z_SCORE * standard_error,
+            },
+            point_estimate,
+            standard_error,
+        }
+    }
+}
+
+impl Estimates {
+    /// Estimates the statistics of durations, computing the standard errors from their
+    /// asymptotic formulas rather than by bootstrapping like Criterion does.
+    fn of(durations: &[Duration]) -> Self {
+        let nanos: Vec<f64> = durations.iter().map(|d| d.as_nanos() as f64).collect();
+        let n = nanos.len() as f64;
+        let mean = nanos.iter().sum::<f64>() / n;
+        let std_dev = match nanos.len() {
+            1 => 0.0,
+            _ => (nanos.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt(),
+        };
+        let median = median(&nanos);
+        // Scaled to estimate the standard deviation of normal samples, as Criterion does
+        let deviations: Vec<f64> = nanos.iter().map(|x| (x - median).abs()).collect();
+        let median_abs_dev = median_of(deviations) * 1.4826;
+
+        let mean_error = std_dev / n.sqrt();
+        Estimates {
+            mean: Estimate::new(mean, mean_error),
+            median: Estimate::new(median, mean_error * 1.2533),
+            median_abs_dev: Estimate::new(median_abs_dev, median_abs_dev / (2.0 * n).sqrt()),
+            slope: None,
+            std_dev: Estimate::new(std_dev, std_dev / (2.0 * (n - 1.0)).max(1.0).sqrt()),
+        }
+    }
+}
+
+/// Returns the median of values.
+fn median(values: &[f64]) -> f64 {
+    median_of(values.to_vec())
+}
+
+/// Returns the median of values, sorting them.
+fn median_of(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    match values.len() % 2 {
+        0 => (values[middle - 1] + values[middle]) / 2.0,
+        _ => values[middle],
+    }
+}
+
+/// Writes the results in the directory layout of Criterion, each benchmark being a group and
+/// each of its revisions a function, so tools reading Criterion's results such as `critcmp` can
+/// read them too.
+///
+/// Every revision that completed an iteration gets a `<benchmark>/<revision>/new` directory with
+/// its `benchmark.json`, `estimates.json` and `sample.json`, under the directory of `--out-file`.
+///
+/// # Arguments
+///
+/// * `term` - Terminal interface for displaying progress and status messages.
+/// * `qbench` - Query benchmark configuration.
+/// * `bench_res` - Query benchmark results to be exported.
+pub fn export(term: &Term, qbench: &QBench, bench_res: &[QueryBenchResult]) -> Result<()> {
+    term.write_line("Exporting results to Criterion's layout...")?;
+    let root = util::out_file(qbench)?;
+
+    for bench in bench_res {
+        for revision in bench.results.iter().filter(|r| !r.durations.is_empty()) {
+            let full_id = format!("{}/{}", bench.name, revision.revision_name);
+            let directory = format!(
+                "{}/{}",
+                util::directory_name(&bench.name),
+                util::directory_name(&revision.revision_name)
+            );
+            let id = BenchmarkId {
+                group_id: bench.name.clone(),
+                function_id: Some(revision.revision_name.clone()),
+                value_str: None,
+                throughput: None,
+                full_id: full_id.clone(),
+                directory_name: directory.clone(),
+                title: full_id,
+            };
+            let sample = Sample {
+                sampling_mode: "Flat",
+                iters: vec![1.0; revision.durations.len()],
+                times: revision.durations.iter().map(|d| d.as_nanos() as f64).collect(),
+            };
+
+            let dir = Path::new(&root).join(&directory).join("new");
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Error creating directory {}", dir.display()))?;
+            write_json(&dir.join("benchmark.json"), &id)?;
+            write_json(&dir.join("estimates.json"), &Estimates::of(&revision.durations))?;
+            write_json(&dir.join("sample.json"), &sample)?;
+        }
+    }
+
+    term.clear_last_lines(1)?;
+    term.write_line(&format!("Results exported to Criterion's layout in {}.", root))?;
+    Ok(())
+}
+
+/// Writes a value to a JSON file.
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let file = fs::File::create(path)
+        .with_context(|| format!("Error creating file {}", path.display()))?;
+    serde_json::to_writer(file, value)?;
+    Ok(())
+}
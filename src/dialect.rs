@@ -0,0 +1,780 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use sqlx::any::AnyKind;
+
+use crate::args::Maintenance;
+use crate::ConcurrencyFailure;
+
+/// Builds the statement asking the database to cancel statements running longer than `timeout`
+/// for the rest of the current transaction or session.
+///
+/// Returns `None` when the engine has no equivalent setting.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `timeout` - The statement timeout, `None` meaning no limit.
+/// * `local` - Whether the setting may be limited to the current transaction.
+pub fn statement_timeout(kind: AnyKind, timeout: Option<Duration>, local: bool) -> Option<String> {
+    match kind {
+        // SET LOCAL only lasts until the wrapping transaction ends (0 disables it)
+        AnyKind::Postgres if local => Some(format!(
+            "SET LOCAL statement_timeout = {}",
+            timeout.map(|t| t.as_millis()).unwrap_or(0)
+        )),
+        AnyKind::Postgres => Some(format!(
+            "SET statement_timeout = {}",
+            timeout.map(|t| t.as_millis()).unwrap_or(0)
+        )),
+        AnyKind::MySql => Some(format!(
+            "SET SESSION max_execution_time = {}",
+            timeout.map(|t| t.as_millis()).unwrap_or(0)
+        )),
+        AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning the server version of the database engine.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+pub fn server_version(kind: AnyKind) -> &'static str {
+    match kind {
+        AnyKind::Postgres => "SELECT version()",
+        AnyKind::MySql => "SELECT CONCAT('MySQL ', VERSION())",
+        AnyKind::Sqlite => "SELECT 'SQLite ' || sqlite_version()",
+        AnyKind::Mssql => "SELECT @@VERSION",
+    }
+}
+
+/// Builds the query returning statistics of the database server as `(name, value)` rows, both
+/// text, the values being numbers.
+///
+/// Gauges, such as the number of active sessions, are sampled as they are, while counters, such
+/// as the committed transactions, grow over the life of the server.
+///
+/// Returns `None` when the engine has no statistics views.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+pub fn server_stats(kind: AnyKind) -> Option<&'static str> {
+    match kind {
+        AnyKind::Postgres => Some(
+            "SELECT s.name, s.value::text FROM pg_stat_database d, LATERAL (VALUES \
+                ('backends', d.numbackends::float8), \
+                ('active_sessions', (SELECT count(*) FROM pg_stat_activity \
+                    WHERE state = 'active')::float8), \
+                ('lock_waiting_sessions', (SELECT count(*) FROM pg_stat_activity \
+                    WHERE wait_event_type = 'Lock')::float8), \
+                ('xact_commit', d.xact_commit::float8), \
+                ('xact_rollback', d.xact_rollback::float8), \
+                ('blks_read', d.blks_read::float8), \
+                ('blks_hit', d.blks_hit::float8), \
+                ('tup_returned', d.tup_returned::float8), \
+                ('tup_fetched', d.tup_fetched::float8), \
+                ('temp_bytes', d.temp_bytes::float8), \
+                ('deadlocks', d.deadlocks::float8)\
+            ) AS s(name, value) WHERE d.datname = current_database()",
+        ),
+        AnyKind::MySql => Some(
+            "SELECT LOWER(VARIABLE_NAME), VARIABLE_VALUE FROM performance_schema.global_status \
+            WHERE VARIABLE_NAME IN ('Threads_connected', 'Threads_running', 'Questions', \
+                'Innodb_buffer_pool_reads', 'Innodb_buffer_pool_read_requests', \
+                'Innodb_row_lock_waits', 'Created_tmp_disk_tables')",
+        ),
+        AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning the blocks of user tables read from disk and found in the buffer
+/// cache, as a single row of heap reads, heap hits, index reads and index hits, all `BIGINT`.
+///
+/// Returns `None` when the engine doesn't count blocks by table.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+pub fn io_stats(kind: AnyKind) -> Option<&'static str> {
+    match kind {
+        AnyKind::Postgres => Some(
+            "SELECT COALESCE(sum(heap_blks_read), 0)::int8, COALESCE(sum(heap_blks_hit), 0)::int8, \
+                COALESCE(sum(idx_blks_read), 0)::int8, COALESCE(sum(idx_blks_hit), 0)::int8 \
+            FROM pg_statio_user_tables",
+        ),
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning the blocks of the database read from disk and found in the buffer
+/// cache over the life of the counters, as a single row of reads and hits, both `BIGINT`.
+///
+/// Returns `None` when the engine has no such counters.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+pub fn cache_stats(kind: AnyKind) -> Option<&'static str> {
+    match kind {
+        AnyKind::Postgres => Some(
+            "SELECT blks_read, blks_hit FROM pg_stat_database WHERE datname = current_database()",
+        ),
+        // Read requests count the reads from the buffer pool, the ones from disk included
+        AnyKind::MySql => Some(
+            "SELECT CAST(SUM(IF(VARIABLE_NAME = 'Innodb_buffer_pool_reads', VARIABLE_VALUE, 0)) \
+                AS SIGNED), \
+            CAST(SUM(IF(VARIABLE_NAME = 'Innodb_buffer_pool_read_requests', VARIABLE_VALUE, \
+                -VARIABLE_VALUE)) AS SIGNED) \
+            FROM performance_schema.global_status WHERE VARIABLE_NAME IN \
+                ('Innodb_buffer_pool_reads', 'Innodb_buffer_pool_read_requests')",
+        ),
+        AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning the size of a table, as a single row of its name, the bytes of
+/// the table and of its indexes, and its estimated number of rows, negative when unknown, all but
+/// the name `BIGINT`. The query returns no row when there is no such table.
+///
+/// Returns `None` when the engine doesn't report the size of tables.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+/// * `table` - Name of the table, optionally qualified by its schema.
+pub fn table_size(kind: AnyKind, table: &str) -> Option<String> {
+    let quoted = format!("'{}'", table.replace('\'', "''"));
+    match kind {
+        AnyKind::Postgres => Some(format!(
+            "SELECT c.oid::regclass::text, pg_table_size(c.oid), pg_indexes_size(c.oid), \
+                c.reltuples::int8 \
+            FROM pg_class c WHERE c.oid = to_regclass({})",
+            quoted
+        )),
+        AnyKind::MySql => Some(format!(
+            "SELECT TABLE_NAME, CAST(DATA_LENGTH AS SIGNED), CAST(INDEX_LENGTH AS SIGNED), \
+                CAST(COALESCE(TABLE_ROWS, -1) AS SIGNED) \
+            FROM information_schema.TABLES \
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = {}",
+            quoted
+        )),
+        AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the statement making the session report its counters to the statistics views as soon
+/// as it is idle, rather than up to several seconds later. Requires Postgres 15 or later.
+///
+/// Returns `None` when the engine reports them right away or has no such counters.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+pub fn flush_stats(kind: AnyKind) -> Option<&'static str> {
+    match kind {
+        AnyKind::Postgres => Some("SELECT pg_stat_force_next_flush()"),
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning the identifier of the session on the database server.
+///
+/// Returns `None` when the engine's wait events can't be followed by session.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+pub fn backend_pid(kind: AnyKind) -> Option<&'static str> {
+    match kind {
+        AnyKind::Postgres => Some("SELECT pg_backend_pid()"),
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning the identifier of the session on the database server, as a
+/// signed 64-bit integer, to cancel its statements from another session with [`cancel_query`].
+///
+/// Returns `None` when the engine's statements can't be cancelled from another session.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+pub fn session_id(kind: AnyKind) -> Option<&'static str> {
+    match kind {
+        AnyKind::Postgres => Some("SELECT pg_backend_pid()::BIGINT"),
+        AnyKind::MySql => Some("SELECT CAST(CONNECTION_ID() AS SIGNED)"),
+        AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the statement cancelling the statement a session is running, leaving the session
+/// itself open.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `id` - Identifier of the session, as returned by [`session_id`].
+pub fn cancel_query(kind: AnyKind, id: i64) -> Option<String> {
+    match kind {
+        AnyKind::Postgres => Some(format!("SELECT pg_cancel_backend({})", id)),
+        AnyKind::MySql => Some(format!("KILL QUERY {}", id)),
+        AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning what a running session is waiting on, if anything, as
+/// `(event_type, event, lock_type, relation)` rows, the last two being null unless it waits for
+/// a lock. Waits for the transaction holding a row lock are reported on the relation of the row.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+/// * `pid` - Identifier of the session, as returned by [`backend_pid`].
+pub fn wait_events(kind: AnyKind, pid: i32) -> Option<String> {
+    match kind {
+        AnyKind::Postgres => Some(format!(
+            "SELECT a.wait_event_type, a.wait_event, l.locktype, \
+                COALESCE(l.relation, (SELECT t.relation FROM pg_locks t WHERE t.pid = a.pid \
+                    AND t.locktype = 'tuple' LIMIT 1))::regclass::text \
+            FROM pg_stat_activity a LEFT JOIN pg_locks l ON l.pid = a.pid AND NOT l.granted \
+            WHERE a.pid = {} AND a.state = 'active' AND a.wait_event_type IS NOT NULL",
+            pid
+        )),
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the query returning the statements of the current database that took the most time in
+/// total, as `(query, calls, mean_ms)` rows of normalized text, `BIGINT` and `DOUBLE`. Requires
+/// the `pg_stat_statements` extension of Postgres 13 or later.
+///
+/// The entries of the same statement, e.g. run by different users, are added up. Transaction
+/// control, session settings, schema changes, maintenance and the reads of the statistics
+/// themselves are left out, they are no candidates for a benchmark.
+///
+/// Returns `None` when the engine doesn't track statements.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the query is built for.
+/// * `limit` - The maximum number of statements returned.
+pub fn top_statements(kind: AnyKind, limit: usize) -> Option<String> {
+    match kind {
+        AnyKind::Postgres => Some(format!(
+            "SELECT s.query, sum(s.calls)::int8, (sum(s.total_exec_time) / sum(s.calls))::float8 \
+            FROM pg_stat_statements s JOIN pg_database d ON d.oid = s.dbid \
+            WHERE d.datname = current_database() AND s.calls > 0 \
+                AND s.query !~* '^\\s*(BEGIN|START|COMMIT|END|ROLLBACK|SAVEPOINT|RELEASE|\
+                    SET|RESET|SHOW|DISCARD|DEALLOCATE|CREATE|ALTER|DROP|TRUNCATE|GRANT|REVOKE|\
+                    ANALYZE|VACUUM|EXPLAIN)\\M' \
+                AND s.query NOT LIKE '%pg_stat_statements%' \
+            GROUP BY s.query ORDER BY sum(s.total_exec_time) DESC LIMIT {}",
+            limit
+        )),
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the statement returning the plan of a statement without executing it, as rows of
+/// text leaving out the estimated costs, so the same plan always reads the same.
+///
+/// Returns `None` when the engine's plans can't be read without their estimates.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `statement` - The statement to explain.
+pub fn explain(kind: AnyKind, statement: &str) -> Option<String> {
+    match kind {
+        AnyKind::Postgres => Some(format!("EXPLAIN (COSTS OFF) {}", statement)),
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the statement changing a setting of the database session.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `name` - Name of the setting.
+/// * `value` - Value of the setting.
+/// * `local` - Whether the setting may be limited to the current transaction.
+pub fn set_setting(kind: AnyKind, name: &str, value: &str, local: bool) -> Result<String> {
+    // Names can't be quoted in every engine, only plain (possibly dotted) ones are accepted
+    let plain = name.split('.').all(|part| {
+        part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    if !plain {
+        return Err(anyhow!("Invalid setting name {:?}", name));
+    }
+    let quoted = quote_literal(kind, value);
+    match kind {
+        AnyKind::Postgres if local => Ok(format!("SET LOCAL {} = {}", name, quoted)),
+        AnyKind::Postgres => Ok(format!("SET {} = {}", name, quoted)),
+        // Numeric system variables reject quoted values
+        AnyKind::MySql => match value.parse::<f64>() {
+            Ok(_) => Ok(format!("SET SESSION {} = {}", name, value)),
+            Err(_) => Ok(format!("SET SESSION {} = {}", name, quoted)),
+        },
+        AnyKind::Sqlite => Ok(format!("PRAGMA {} = {}", name, quoted)),
+        AnyKind::Mssql => Err(anyhow!("Session settings are not supported on {:?}", kind)),
+    }
+}
+
+/// Returns the setting naming the client of a session, as the server reports it in its activity
+/// views and logs.
+///
+/// Returns `None` when the engine can't rename a session once connected.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine.
+pub fn application_name_setting(kind: AnyKind) -> Option<&'static str> {
+    match kind {
+        AnyKind::Postgres => Some("application_name"),
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the statement restoring the default of a setting changed with [`set_setting`], when
+/// it outlives the revision.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `name` - Name of the setting.
+/// * `local` - Whether the setting was limited to the current transaction.
+pub fn reset_setting(kind: AnyKind, name: &str, local: bool) -> Option<String> {
+    match kind {
+        AnyKind::Postgres if local => None,
+        AnyKind::Postgres => Some(format!("RESET {}", name)),
+        AnyKind::MySql => Some(format!("SET SESSION {} = DEFAULT", name)),
+        AnyKind::Sqlite | AnyKind::Mssql => None,
+    }
+}
+
+/// Builds the statement setting the schema unqualified names are resolved in for the rest of
+/// the session, i.e. the `search_path` on Postgres and the database on MySQL.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `schema` - Name of the schema, or database.
+pub fn set_default_schema(kind: AnyKind, schema: &str) -> Result<String> {
+    match kind {
+        AnyKind::Postgres => Ok(format!("SET search_path TO {}", quote_identifier(kind, schema))),
+        AnyKind::MySql => Ok(format!("USE {}", quote_identifier(kind, schema))),
+        AnyKind::Sqlite | AnyKind::Mssql => {
+            Err(anyhow!("Setting the schema is not supported on {:?}", kind))
+        }
+    }
+}
+
+/// Builds the statement creating the schema an isolated revision runs in.
+///
+/// Only Postgres is supported, an error is returned for other engines.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `schema` - Name of the schema.
+pub fn create_schema(kind: AnyKind, schema: &str) -> Result<String> {
+    match kind {
+        AnyKind::Postgres => Ok(format!("CREATE SCHEMA {}", quote_identifier(kind, schema))),
+        _ => Err(unsupported_isolation(kind)),
+    }
+}
+
+/// Builds the statement resolving unqualified names in the given schema first, falling back
+/// to the shared one.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `schema` - Name of the schema.
+/// * `fallback` - The shared schema, `public` when `None`.
+/// * `local` - Whether the setting may be limited to the current transaction.
+pub fn use_schema(
+    kind: AnyKind,
+    schema: &str,
+    fallback: Option<&str>,
+    local: bool,
+) -> Result<String> {
+    let schema = quote_identifier(kind, schema);
+    let fallback = quote_identifier(kind, fallback.unwrap_or("public"));
+    match kind {
+        AnyKind::Postgres if local => {
+            Ok(format!("SET LOCAL search_path TO {}, {}", schema, fallback))
+        }
+        AnyKind::Postgres => Ok(format!("SET search_path TO {}, {}", schema, fallback)),
+        _ => Err(unsupported_isolation(kind)),
+    }
+}
+
+/// Builds the statement dropping an isolated schema along with everything created in it.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `schema` - Name of the schema.
+pub fn drop_schema(kind: AnyKind, schema: &str) -> Result<String> {
+    match kind {
+        AnyKind::Postgres => Ok(format!(
+            "DROP SCHEMA IF EXISTS {} CASCADE",
+            quote_identifier(kind, schema)
+        )),
+        _ => Err(unsupported_isolation(kind)),
+    }
+}
+
+/// Builds the statement pointing the session back at the default schema.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `default` - The schema set with `--schema`, the server default when `None`.
+pub fn reset_schema(kind: AnyKind, default: Option<&str>) -> Result<String> {
+    match (kind, default) {
+        (_, Some(default)) => set_default_schema(kind, default),
+        (AnyKind::Postgres, None) => Ok("RESET search_path".to_string()),
+        _ => Err(unsupported_isolation(kind)),
+    }
+}
+
+fn unsupported_isolation(kind: AnyKind) -> anyhow::Error {
+    anyhow!("Isolated schemas are not supported on {:?}", kind)
+}
+
+/// Builds the statement running maintenance on a table before a revision is timed.
+///
+/// Vacuuming is only supported on Postgres, outside of a transaction. On MySQL, where
+/// `ANALYZE TABLE` implicitly commits, analyzing is only supported outside of a transaction too.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `table` - Name of the table.
+/// * `maintenance` - The maintenance to run.
+/// * `in_transaction` - Whether the statement runs inside the revision's transaction.
+pub fn maintain_table(
+    kind: AnyKind,
+    table: &str,
+    maintenance: Maintenance,
+    in_transaction: bool,
+) -> Result<String> {
+    match (kind, maintenance) {
+        (AnyKind::Postgres, Maintenance::Analyze) => Ok(format!("ANALYZE {}", table)),
+        (AnyKind::Postgres, Maintenance::Vacuum) if !in_transaction => {
+            Ok(format!("VACUUM (ANALYZE) {}", table))
+        }
+        (AnyKind::MySql, Maintenance::Analyze) if !in_transaction => {
+            Ok(format!("ANALYZE TABLE {}", table))
+        }
+        (AnyKind::Sqlite, Maintenance::Analyze) => Ok(format!("ANALYZE {}", table)),
+        (AnyKind::Mssql, Maintenance::Analyze) => Ok(format!("UPDATE STATISTICS {}", table)),
+        (AnyKind::Postgres, Maintenance::Vacuum) => Err(anyhow!(
+            "VACUUM can't run inside the revision's transaction, use autocommit"
+        )),
+        (AnyKind::MySql, Maintenance::Analyze) => Err(anyhow!(
+            "ANALYZE TABLE would commit the revision's transaction on MySQL, use autocommit"
+        )),
+        (_, Maintenance::Vacuum) => Err(anyhow!("Vacuuming is not supported on {:?}", kind)),
+    }
+}
+
+/// Builds the statement declaring the cursor a query's rows are fetched through.
+///
+/// Cursors are only supported on Postgres, an error is returned for other engines.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `name` - Name of the cursor.
+/// * `sql` - The query whose rows the cursor returns.
+/// * `hold` - Whether the cursor must outlive the current transaction, as needed outside of one.
+pub fn declare_cursor(kind: AnyKind, name: &str, sql: &str, hold: bool) -> Result<String> {
+    let sql = sql.trim().trim_end_matches(';');
+    match kind {
+        AnyKind::Postgres if hold => {
+            Ok(format!("DECLARE {} NO SCROLL CURSOR WITH HOLD FOR {}", name, sql))
+        }
+        AnyKind::Postgres => Ok(format!("DECLARE {} NO SCROLL CURSOR FOR {}", name, sql)),
+        _ => Err(anyhow!("Fetching through a cursor is not supported on {:?}", kind)),
+    }
+}
+
+/// Builds the statement fetching the next `count` rows of a cursor declared with
+/// [`declare_cursor`].
+///
+/// # Arguments
+///
+/// * `name` - Name of the cursor.
+/// * `count` - Number of rows to fetch.
+pub fn fetch_from_cursor(name: &str, count: usize) -> String {
+    format!("FETCH FORWARD {} FROM {}", count, name)
+}
+
+/// Builds the statement closing a cursor declared with [`declare_cursor`].
+///
+/// # Arguments
+///
+/// * `name` - Name of the cursor.
+pub fn close_cursor(name: &str) -> String {
+    format!("CLOSE {}", name)
+}
+
+/// Builds the statements resetting the session state (cached plans and settings) that can be
+/// executed inside a transaction.
+///
+/// `DISCARD ALL` is not used on Postgres as it is not allowed inside a transaction block.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statements are built for.
+pub fn reset_session(kind: AnyKind) -> &'static [&'static str] {
+    match kind {
+        AnyKind::Postgres => &["DISCARD PLANS", "RESET ALL"],
+        AnyKind::MySql | AnyKind::Sqlite | AnyKind::Mssql => &[],
+    }
+}
+
+/// Checks whether the error is the database cancelling a statement because of a timeout.
+///
+/// # Arguments
+///
+/// * `error` - The error returned while executing a statement.
+pub fn is_timeout_error(error: &sqlx::Error) -> bool {
+    let Some(db_error) = error.as_database_error() else {
+        return false;
+    };
+    match db_error.code().as_deref() {
+        // Postgres query_canceled
+        Some("57014") => true,
+        // MySQL reports ER_QUERY_TIMEOUT (3024) with the generic HY000 state
+        Some("HY000") => db_error.message().contains("maximum statement execution time"),
+        _ => false,
+    }
+}
+
+/// Classifies the error as the concurrency failure it reports, if it is a transient failure
+/// such as a deadlock or a serialization failure, likely to succeed when the statement is retried.
+///
+/// # Arguments
+///
+/// * `error` - The error returned while executing a statement.
+pub fn concurrency_failure(error: &sqlx::Error) -> Option<ConcurrencyFailure> {
+    let db_error = error.as_database_error()?;
+    match db_error.code().as_deref() {
+        Some("40P01") => Some(ConcurrencyFailure::Deadlock),
+        // MySQL reports its deadlocks (ER_LOCK_DEADLOCK) as serialization failures
+        Some("40001") if db_error.message().contains("Deadlock found") => {
+            Some(ConcurrencyFailure::Deadlock)
+        }
+        Some("40001") => Some(ConcurrencyFailure::Serialization),
+        // SQLITE_BUSY and SQLITE_LOCKED
+        Some("5") | Some("6") => Some(ConcurrencyFailure::Busy),
+        _ if db_error.message().contains("deadlock victim") => Some(ConcurrencyFailure::Deadlock),
+        _ => None,
+    }
+}
+
+/// Checks whether the error means the connection to the database was lost or refused, so a
+/// new connection is likely to succeed.
+///
+/// # Arguments
+///
+/// * `error` - The error returned while connecting or executing a statement.
+pub fn is_connection_error(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_) => true,
+        // Postgres connection_exception class and admin/crash shutdowns
+        sqlx::Error::Database(db_error) => db_error
+            .code()
+            .is_some_and(|code| code.starts_with("08") || code == "57P01" || code == "57P02"),
+        _ => false,
+    }
+}
+
+/// Builds the statement creating the savepoint a failed iteration is rolled back to.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `name` - Name of the savepoint.
+pub fn savepoint(kind: AnyKind, name: &str) -> String {
+    match kind {
+        AnyKind::Mssql => format!("SAVE TRANSACTION {}", name),
+        _ => format!("SAVEPOINT {}", name),
+    }
+}
+
+/// Builds the statement rolling back to a savepoint created with [`savepoint`].
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `name` - Name of the savepoint.
+pub fn rollback_to_savepoint(kind: AnyKind, name: &str) -> String {
+    match kind {
+        AnyKind::Mssql => format!("ROLLBACK TRANSACTION {}", name),
+        _ => format!("ROLLBACK TO SAVEPOINT {}", name),
+    }
+}
+
+/// Builds the statement releasing a savepoint created with [`savepoint`], if the engine
+/// supports releasing savepoints.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the statement is built for.
+/// * `name` - Name of the savepoint.
+pub fn release_savepoint(kind: AnyKind, name: &str) -> Option<String> {
+    match kind {
+        AnyKind::Mssql => None,
+        _ => Some(format!("RELEASE SAVEPOINT {}", name)),
+    }
+}
+
+/// Quotes an identifier, e.g. a table or column name, so reserved words and special characters
+/// are taken as they are. The parts of a qualified name like `public.users` are quoted one by
+/// one.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the identifier is quoted for.
+/// * `name` - The identifier.
+pub fn quote_identifier(kind: AnyKind, name: &str) -> String {
+    let (open, close) = match kind {
+        AnyKind::MySql => ('`', '`'),
+        AnyKind::Mssql => ('[', ']'),
+        AnyKind::Postgres | AnyKind::Sqlite => ('"', '"'),
+    };
+    name.split('.')
+        .map(|part| {
+            let escaped = part.replace(close, &format!("{}{}", close, close));
+            format!("{}{}{}", open, escaped, close)
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Quotes a value as a string literal. MySQL takes backslashes in literals as escapes, they
+/// are escaped there as well.
+///
+/// # Arguments
+///
+/// * `kind` - The database engine the literal is built for.
+/// * `value` - The value.
+pub fn quote_literal(kind: AnyKind, value: &str) -> String {
+    let escaped = match kind {
+        AnyKind::MySql => value.replace('\\', "\\\\").replace('\'', "''"),
+        AnyKind::Postgres | AnyKind::Sqlite | AnyKind::Mssql => value.replace('\'', "''"),
+    };
+    format!("'{}'", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setting_names_must_be_plain() {
+        assert_eq!(
+            set_setting(AnyKind::Postgres, "pg_trgm.similarity_threshold", "0.5", false).unwrap(),
+            "SET pg_trgm.similarity_threshold = '0.5'"
+        );
+        assert!(set_setting(AnyKind::Postgres, "work_mem = 1; DROP TABLE t", "1", true).is_err());
+        assert!(set_setting(AnyKind::MySql, "1sort_buffer_size", "1", false).is_err());
+        assert!(set_setting(AnyKind::MySql, "", "1", false).is_err());
+    }
+
+    #[test]
+    fn default_schemas_are_quoted() {
+        assert_eq!(
+            set_default_schema(AnyKind::Postgres, "App").unwrap(),
+            r#"SET search_path TO "App""#
+        );
+        assert_eq!(set_default_schema(AnyKind::MySql, "app`db").unwrap(), "USE `app``db`");
+    }
+
+    #[test]
+    fn isolated_schemas_are_quoted() {
+        assert_eq!(
+            create_schema(AnyKind::Postgres, r#"qbench "x""#).unwrap(),
+            r#"CREATE SCHEMA "qbench ""x""""#
+        );
+        assert_eq!(
+            use_schema(AnyKind::Postgres, "qbench_1", Some("App"), true).unwrap(),
+            r#"SET LOCAL search_path TO "qbench_1", "App""#
+        );
+        assert_eq!(
+            drop_schema(AnyKind::Postgres, "qbench_1").unwrap(),
+            r#"DROP SCHEMA IF EXISTS "qbench_1" CASCADE"#
+        );
+    }
+
+    #[test]
+    fn statement_timeouts_are_set_in_milliseconds() {
+        let timeout = Some(Duration::from_secs(2));
+        assert_eq!(
+            statement_timeout(AnyKind::Postgres, timeout, true),
+            Some("SET LOCAL statement_timeout = 2000".to_string())
+        );
+        // Session settings outlive the revision, they are reset without a timeout
+        assert_eq!(
+            statement_timeout(AnyKind::Postgres, None, false),
+            Some("SET statement_timeout = 0".to_string())
+        );
+        assert_eq!(
+            statement_timeout(AnyKind::MySql, None, true),
+            Some("SET SESSION max_execution_time = 0".to_string())
+        );
+        assert_eq!(statement_timeout(AnyKind::Sqlite, timeout, false), None);
+    }
+
+    #[test]
+    fn settings_are_changed_in_the_session() {
+        assert_eq!(
+            set_setting(AnyKind::Postgres, "work_mem", "256MB", true).unwrap(),
+            "SET LOCAL work_mem = '256MB'"
+        );
+        assert_eq!(
+            set_setting(AnyKind::MySql, "sort_buffer_size", "262144", false).unwrap(),
+            "SET SESSION sort_buffer_size = 262144"
+        );
+        assert_eq!(
+            set_setting(AnyKind::MySql, "sql_mode", "ANSI", false).unwrap(),
+            "SET SESSION sql_mode = 'ANSI'"
+        );
+        assert!(set_setting(AnyKind::Mssql, "work_mem", "1", false).is_err());
+    }
+
+    #[test]
+    fn cursors_are_only_declared_on_postgres() {
+        assert_eq!(
+            declare_cursor(AnyKind::Postgres, "c", "SELECT 1;", false).unwrap(),
+            "DECLARE c NO SCROLL CURSOR FOR SELECT 1"
+        );
+        assert_eq!(
+            declare_cursor(AnyKind::Postgres, "c", " SELECT 1 ", true).unwrap(),
+            "DECLARE c NO SCROLL CURSOR WITH HOLD FOR SELECT 1"
+        );
+        assert!(declare_cursor(AnyKind::MySql, "c", "SELECT 1", false).is_err());
+    }
+
+    #[test]
+    fn queries_are_cancelled_leaving_their_session_open() {
+        assert_eq!(
+            cancel_query(AnyKind::Postgres, 42),
+            Some("SELECT pg_cancel_backend(42)".to_string())
+        );
+        assert_eq!(
+            cancel_query(AnyKind::MySql, 42),
+            Some("KILL QUERY 42".to_string())
+        );
+        assert_eq!(cancel_query(AnyKind::Sqlite, 42), None);
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Errors returned by the library API, telling what failed so callers can react to the kind
+/// of failure rather than to the message.
+#[derive(Debug, Error)]
+pub enum QBenchError {
+    /// A config file could not be read or parsed.
+    #[error("Error loading config file {}", path.display())]
+    Config {
+        path: PathBuf,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// The database could not be reached or the connection options are invalid.
+    #[error("Could not connect to the database at {url}")]
+    Connection {
+        /// The connection URL, with its password redacted.
+        url: String,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// A suite script, or a pre or post script of a revision, failed.
+    #[error("Error executing {script}{}", location(bench.as_deref(), revision.as_deref(), *iteration))]
+    Script {
+        /// Which script failed, e.g. `suite setup script` or `Pre-Script`.
+        script: String,
+        bench: Option<String>,
+        revision: Option<String>,
+        /// The iteration, counted from 1, of scripts running around each iteration.
+        iteration: Option<usize>,
+        /// The statement of the script that failed, if the database rejected one.
+        statement: Option<String>,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// The benchmarked query of a revision failed.
+    #[error("Error executing query{}", location(bench.as_deref(), Some(revision), *iteration))]
+    Query {
+        bench: Option<String>,
+        revision: String,
+        /// The iteration, counted from 1, the query failed on, if it failed while timed.
+        iteration: Option<usize>,
+        /// The statement of the query that failed, if the database rejected one.
+        statement: Option<String>,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// Any other failure, e.g. an invalid filter pattern or fixture.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl QBenchError {
+    /// Sets the benchmark a script or query failure happened in.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the benchmark.
+    pub(crate) fn in_bench(mut self, name: &str) -> Self {
+        match &mut self {
+            QBenchError::Script { bench, .. } | QBenchError::Query { bench, .. } => {
+                *bench = Some(name.to_string());
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+/// Recovers the typed error an internal error was created from, other errors being kept as
+/// [`QBenchError::Other`].
+impl From<anyhow::Error> for QBenchError {
+    fn from(error: anyhow::Error) -> Self {
+        error.downcast::<QBenchError>().unwrap_or_else(QBenchError::Other)
+    }
+}
+
+/// Describes where a script or query failed, e.g. ` for revision v2 of benchmark users on
+/// iteration 3`.
+///
+/// # Arguments
+///
+/// * `bench` - Name of the benchmark, if known.
+/// * `revision` - Name of the revision, if any.
+/// * `iteration` - The iteration, counted from 1, if any.
+fn location(bench: Option<&str>, revision: Option<&str>, iteration: Option<usize>) -> String {
+    let mut location = String::new();
+    if let Some(revision) = revision {
+        location.push_str(&format!(" for revision {}", revision));
+    }
+    if let Some(bench) = bench {
+        location.push_str(&format!(" of benchmark {}", bench));
+    }
+    if let Some(iteration) = iteration {
+        location.push_str(&format!(" on iteration {}", iteration));
+    }
+    location
+}
@@ -0,0 +1,328 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use sqlx::any::{AnyConnectionKind, AnyKind};
+use sqlx::postgres::{PgConnection, PgCopyIn};
+use sqlx::{query, AnyConnection};
+use tokio::fs::{read_to_string, File};
+
+use crate::dialect;
+use crate::util::extract_multiline_queries;
+
+/// Number of CSV rows inserted by a single `INSERT` statement.
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// A file loaded into the database before a benchmark runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fixture {
+    /// SQL statements executed as they are.
+    Sql(PathBuf),
+    /// CSV rows, with a header row naming the columns, inserted into a table.
+    Csv { path: PathBuf, table: String },
+}
+
+impl Fixture {
+    /// Parses a fixture entry of a benchmark configuration.
+    ///
+    /// Entries are either a `.sql` file, or a `.csv` file optionally followed by `:table`,
+    /// the table defaulting to the name of the file. Relative paths are resolved against `dir`.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - The fixture entry, e.g. `users.csv:users`.
+    /// * `dir` - Directory relative paths are resolved against.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// use qbench::fixture::Fixture;
+    ///
+    /// let fixture = Fixture::parse("users.csv:app_users", Path::new("benches"))?;
+    /// assert_eq!(
+    ///     fixture,
+    ///     Fixture::Csv { path: PathBuf::from("benches/users.csv"), table: "app_users".to_string() }
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn parse(spec: &str, dir: &Path) -> Result<Self> {
+        let (file, table) = match spec.rsplit_once(':') {
+            Some((file, table)) if file.ends_with(".csv") => (file, Some(table)),
+            _ => (spec, None),
+        };
+        let path = dir.join(file);
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("sql") => Ok(Fixture::Sql(path)),
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => {
+                let table = match table {
+                    Some(table) => table.to_string(),
+                    None => path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .ok_or_else(|| anyhow!("Invalid fixture file: {}", spec))?,
+                };
+                Ok(Fixture::Csv { path, table })
+            }
+            _ => Err(anyhow!(
+                "Unsupported fixture file: {}, expected a .sql or .csv file",
+                spec
+            )),
+        }
+    }
+
+    /// Loads the fixture using the given connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection, or transaction, the fixture is loaded with.
+    pub async fn load(&self, conn: &mut AnyConnection) -> Result<()> {
+        match self {
+            Fixture::Sql(path) => {
+                let script = read_fixture(path).await?;
+                for statement in extract_multiline_queries(&script, conn.kind()) {
+                    let _ = query(statement).execute(&mut *conn).await?;
+                }
+            }
+            Fixture::Csv { path, table } => {
+                // Postgres streams the file as it is with COPY, far faster than INSERTs
+                if let AnyConnectionKind::Postgres(conn) = conn.private_get_mut() {
+                    return copy_csv(conn, path, table).await;
+                }
+
+                let content = read_fixture(path).await?;
+                load_csv(conn, table, &content)
+                    .await
+                    .with_context(|| format!("Invalid CSV fixture {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the tables the fixture loads rows into: the table of a CSV fixture, or the
+    /// tables of the `INSERT`, `COPY` and `CREATE TABLE` statements of a SQL fixture.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of database the fixture is loaded into.
+    pub async fn tables(&self, kind: AnyKind) -> Result<Vec<String>> {
+        match self {
+            Fixture::Sql(path) => {
+                let script = read_fixture(path).await?;
+                let mut tables = vec![];
+                for statement in extract_multiline_queries(&script, kind) {
+                    if let Some(table) = written_table(statement) {
+                        if !tables.contains(&table) {
+                            tables.push(table);
+                        }
+                    }
+                }
+                Ok(tables)
+            }
+            Fixture::Csv { table, .. } => Ok(vec![table.clone()]),
+        }
+    }
+}
+
+/// Returns the table a statement loads rows into, if it is an `INSERT INTO`, `COPY` or
+/// `CREATE TABLE` statement.
+fn written_table(statement: &str) -> Option<String> {
+    let mut words = statement.split_whitespace();
+    let is = |word: &str, keyword: &str| word.eq_ignore_ascii_case(keyword);
+    let first = words.next()?;
+    let table = if (is(first, "insert") && is(words.next()?, "into")) || is(first, "copy") {
+        words.next()?
+    } else if is(first, "create") {
+        // Skip the modifiers, e.g. CREATE UNLOGGED TABLE IF NOT EXISTS
+        let mut word = words.next()?;
+        while !is(word, "table") {
+            if !["temp", "temporary", "unlogged"].iter().any(|modifier| is(word, modifier)) {
+                return None;
+            }
+            word = words.next()?;
+        }
+        match words.next()? {
+            word if is(word, "if") => words.nth(2)?,
+            word => word,
+        }
+    } else {
+        return None;
+    };
+    // The column list may follow the name without a space
+    let table = table.split('(').next()?;
+    (!table.is_empty()).then(|| table.to_string())
+}
+
+/// Reads the content of a fixture file.
+async fn read_fixture(path: &Path) -> Result<String> {
+    read_to_string(path)
+        .await
+        .with_context(|| format!("Error reading fixture file {}", path.display()))
+}
+
+/// Loads CSV content into a table, with `COPY FROM STDIN` on Postgres and batched `INSERT`
+/// statements on other engines.
+///
+/// # Arguments
+///
+/// * `conn` - The connection, or transaction, the rows are loaded with.
+/// * `table` - The table the rows are loaded into.
+/// * `content` - CSV content, its header row naming the columns.
+pub async fn load_csv(conn: &mut AnyConnection, table: &str, content: &str) -> Result<()> {
+    let kind = conn.kind();
+    if let AnyConnectionKind::Postgres(conn) = conn.private_get_mut() {
+        let columns = csv_columns(&mut csv::Reader::from_reader(content.as_bytes()), kind)?;
+        let mut copy = conn.copy_in_raw(&copy_statement(table, &columns)).await?;
+        return finish_copy(copy.send(content.as_bytes()).await.map(|_| ()), copy).await;
+    }
+
+    for statement in csv_inserts(content, table, kind)? {
+        let _ = query(&statement).execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Streams a CSV file into a table with `COPY FROM STDIN`.
+///
+/// # Arguments
+///
+/// * `conn` - The Postgres connection, or transaction, the file is loaded with.
+/// * `path` - Path of the CSV file, its header row naming the columns.
+/// * `table` - The table the rows are copied into.
+async fn copy_csv(conn: &mut PgConnection, path: &Path, table: &str) -> Result<()> {
+    let columns = csv::Reader::from_path(path)
+        .and_then(|mut reader| csv_columns(&mut reader, AnyKind::Postgres))
+        .with_context(|| format!("Invalid CSV fixture {}", path.display()))?;
+    let file = File::open(path)
+        .await
+        .with_context(|| format!("Error reading fixture file {}", path.display()))?;
+
+    let mut copy = conn.copy_in_raw(&copy_statement(table, &columns)).await?;
+    finish_copy(copy.read_from(file).await.map(|_| ()), copy).await
+}
+
+/// Builds the `COPY FROM STDIN` statement for CSV content with a header row.
+fn copy_statement(table: &str, columns: &str) -> String {
+    format!(
+        "COPY {} ({}) FROM STDIN WITH (FORMAT csv, HEADER true)",
+        dialect::quote_identifier(AnyKind::Postgres, table),
+        columns
+    )
+}
+
+/// Completes a `COPY FROM STDIN` once its data is sent, or aborts it when sending failed, as
+/// the connection is left unusable otherwise.
+///
+/// # Arguments
+///
+/// * `sent` - Outcome of sending the data.
+/// * `copy` - The copy the data was sent to.
+async fn finish_copy(
+    sent: sqlx::Result<()>,
+    copy: PgCopyIn<&mut PgConnection>,
+) -> Result<()> {
+    match sent {
+        Ok(()) => {
+            let _ = copy.finish().await?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = copy.abort(e.to_string()).await;
+            Err(e.into())
+        }
+    }
+}
+
+/// Builds the batched `INSERT` statements loading CSV rows into a table.
+///
+/// Values are inserted as string literals, leaving their conversion to the column type to the
+/// database. Empty values are inserted as `NULL`.
+///
+/// # Arguments
+///
+/// * `content` - CSV content, its header row naming the columns.
+/// * `table` - The table the rows are inserted into.
+/// * `kind` - The database engine the statements are built for.
+fn csv_inserts(content: &str, table: &str, kind: AnyKind) -> Result<Vec<String>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let columns = csv_columns(&mut reader, kind)?;
+    let table = dialect::quote_identifier(kind, table);
+
+    let mut statements = vec![];
+    let mut rows = vec![];
+    for record in reader.records() {
+        let values: Vec<String> = record?
+            .iter()
+            .map(|value| match value {
+                "" => "NULL".to_string(),
+                value => dialect::quote_literal(kind, value),
+            })
+            .collect();
+        rows.push(format!("({})", values.join(", ")));
+
+        if rows.len() == INSERT_BATCH_SIZE {
+            statements.push(insert_statement(&table, &columns, &rows));
+            rows.clear();
+        }
+    }
+    if !rows.is_empty() {
+        statements.push(insert_statement(&table, &columns, &rows));
+    }
+    Ok(statements)
+}
+
+/// Reads the column list from the header row of a CSV file, quoted for the given engine.
+fn csv_columns<R: std::io::Read>(
+    reader: &mut csv::Reader<R>,
+    kind: AnyKind,
+) -> csv::Result<String> {
+    let columns: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|column| dialect::quote_identifier(kind, column.trim()))
+        .collect();
+    Ok(columns.join(", "))
+}
+
+/// Builds an `INSERT` statement for already formatted rows.
+fn insert_statement(table: &str, columns: &str, rows: &[String]) -> String {
+    format!("INSERT INTO {} ({}) VALUES {}", table, columns, rows.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_quote_identifiers_and_escape_values() {
+        let content = "id,order\n1,it's C:\\temp\n2,\n";
+        let statements = csv_inserts(content, "app.items", AnyKind::MySql).unwrap();
+        let mysql = "INSERT INTO `app`.`items` (`id`, `order`) \
+            VALUES ('1', 'it''s C:\\\\temp'), ('2', NULL)";
+        assert_eq!(statements, vec![mysql]);
+
+        let statements = csv_inserts(content, "items", AnyKind::Postgres).unwrap();
+        let postgres = "INSERT INTO \"items\" (\"id\", \"order\") \
+            VALUES ('1', 'it''s C:\\temp'), ('2', NULL)";
+        assert_eq!(statements, vec![postgres]);
+    }
+
+    #[test]
+    fn finds_the_table_a_statement_writes() {
+        assert_eq!(
+            written_table("INSERT INTO users(id) VALUES (1)"),
+            Some("users".to_string())
+        );
+        assert_eq!(
+            written_table("copy orders FROM stdin"),
+            Some("orders".to_string())
+        );
+        assert_eq!(
+            written_table("CREATE UNLOGGED TABLE IF NOT EXISTS events (id int)"),
+            Some("events".to_string())
+        );
+        assert_eq!(written_table("CREATE INDEX ON users (id)"), None);
+        assert_eq!(written_table("SELECT 1"), None);
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::util::relative_std_dev;
+use crate::{QueryBenchResult, RunNoise};
+
+/// Number of most recent runs of the history the run-to-run noise is estimated over.
+const NOISE_WINDOW: usize = 10;
+
+/// A run recorded in the history file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// When the run started, in RFC 3339 format.
+    pub started_at: String,
+    pub results: Vec<QueryBenchResult>,
+}
+
+/// Appends a run to the history file, one JSON document per line, creating the file if needed.
+///
+/// # Arguments
+///
+/// * `path` - Path of the history file.
+/// * `entry` - The run to record.
+pub fn append(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Error opening history file {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Loads the runs recorded in the history file, oldest first, none when it doesn't exist yet.
+///
+/// A truncated last line, left behind when the process died mid-write, is ignored.
+///
+/// # Arguments
+///
+/// * `path` - Path of the history file.
+pub fn load(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context(format!("Error reading history file {}", path.display())))
+        }
+    };
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    let mut entries = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) if index == lines.len() - 1 => break,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context(format!(
+                    "Invalid history entry on line {} of {}",
+                    index + 1,
+                    path.display()
+                )))
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Estimates how much the latency of each revision varies within the run, and from one run to
+/// the next, so differences can be told apart from noise.
+///
+/// The run-to-run noise comes from the rounds of the run when there were several, or else from
+/// the average latencies of the revision in the last runs of the history and this one.
+///
+/// # Arguments
+///
+/// * `results` - The results of the run, updated with the noise of each revision.
+/// * `history` - The runs recorded before this one, oldest first.
+pub fn estimate_noise(results: &mut [QueryBenchResult], history: &[HistoryEntry]) {
+    let recent = &history[history.len().saturating_sub(NOISE_WINDOW - 1)..];
+    for bench in results {
+        for revision in &mut bench.results {
+            let Some(within) = relative_std_dev(&revision.durations) else {
+                continue;
+            };
+            let averages = match revision.round_averages.len() {
+                0 | 1 => {
+                    let mut averages: Vec<_> = recent
+                        .iter()
+                        .filter_map(|entry| {
+                            let past = entry.results.iter().find(|b| b.name == bench.name)?;
+                            past.results
+                                .iter()
+                                .find(|r| {
+                                    r.revision_name == revision.revision_name
+                                        && !r.durations.is_empty()
+                                })
+                                .map(|r| r.avg_query_duration)
+                        })
+                        .collect();
+                    averages.push(revision.avg_query_duration);
+                    averages
+                }
+                _ => revision.round_averages.clone(),
+            };
+            revision.noise = Some(RunNoise {
+                within,
+                between: relative_std_dev(&averages),
+                runs: averages.len(),
+            });
+        }
+    }
+}
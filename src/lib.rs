@@ -1,37 +1,124 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::num::NonZeroUsize;
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use serde_with::{DurationNanoSeconds, serde_as};
 use tabled::Tabled;
 use tokio::time::Duration;
 
+use crate::args::{Maintenance, QueryProtocol, ThinkTime};
+
 pub mod args;
 pub mod bench;
+pub mod builder;
+pub mod capture;
+#[cfg(feature = "charts")]
+pub mod chart;
+pub mod checkpoint;
+pub mod checksum;
+pub mod compare;
+pub mod container;
+pub mod coordinator;
+pub mod criterion;
+mod dialect;
+pub mod error;
+pub mod fixture;
+pub mod history;
+pub mod init;
+pub mod markdown;
 pub mod util;
-mod parser;
+pub mod parser;
+pub mod plan;
+pub mod redact;
+pub mod remote;
+pub mod report;
+pub mod sampler;
+pub mod schedule;
+pub mod seed;
+pub mod server;
+pub mod slowlog;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod upload;
 
 // Define a struct to hold a single benchmark result, including revision-specific results.
-#[derive(Serialize, Debug, Clone, Default, Tabled)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Tabled)]
 #[tabled(rename_all = "PascalCase")]
 pub struct QueryBenchResult {
     pub name: String,
+    #[tabled(display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+    /// Name of the suite the benchmark belongs to, if any.
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub suite: Option<String>,
+    /// Tags of the benchmark, its suite's included.
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
     #[tabled(display_with = "util::format_rev_result")]
     pub results: Vec<QueryRevisionResult>,
+    #[tabled(rename = "CacheHit", display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cache_hit_ratio: Option<CacheHitRatio>,
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub table_sizes: Option<TableSizes>,
 }
 
 // Define an enum to represent different types of query revision results.
 #[serde_as]
-#[derive(Serialize, Debug, Clone, Default, Tabled)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Tabled)]
 #[tabled(rename_all = "PascalCase")]
 pub struct QueryRevisionResult {
     pub revision_name: String,
 
+    #[tabled(display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+
+    #[serde(default)]
+    pub status: RevisionStatus,
+
+    #[tabled(display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub message: Option<String>,
+
     #[tabled(skip)]
     #[serde_as(as = "Vec<DurationNanoSeconds<u64>>")]
     #[serde(rename = "durations_ns")]
     pub durations: Vec<Duration>,
 
+    /// When each sample started, in microseconds since the Unix epoch, in the order of
+    /// `durations`.
+    #[tabled(skip)]
+    #[serde(rename = "timestamps_us", skip_serializing_if = "Vec::is_empty", default)]
+    pub timestamps: Vec<u64>,
+
+    #[serde(default)]
+    pub retries: usize,
+
+    #[tabled(display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rows: Option<u64>,
+
+    #[tabled(display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fetch_chunks: Option<u64>,
+
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub checksum: Option<String>,
+
+    #[tabled(display_with = "util::format_duration_pretty")]
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "acquire_duration_ns", default)]
+    pub acquire_duration: Duration,
+
     #[tabled(display_with = "util::format_duration_pretty")]
     #[serde_as(as = "DurationNanoSeconds<u64>")]
     #[serde(rename = "avg_query_duration_ns")]
@@ -46,32 +133,799 @@ pub struct QueryRevisionResult {
     #[serde_as(as = "DurationNanoSeconds<u64>")]
     #[serde(rename = "post_script_duration_ns")]
     pub post_script_duration: Duration,
+
+    #[tabled(display_with = "util::format_duration_pretty")]
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "avg_hook_duration_ns", default)]
+    pub avg_hook_duration: Duration,
+
+    /// Wall time of the iterations, from the first to the end of the last one that completed.
+    #[tabled(rename = "TimeSplit", display_with("util::format_time_split", self))]
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "iterations_duration_ns", default)]
+    pub iterations_duration: Duration,
+
+    /// Durations of the executions on a new connection after dropping caches, before the
+    /// iterations.
+    #[tabled(rename = "ColdStart", display_with("util::format_cold_start", self))]
+    #[serde_as(as = "Vec<DurationNanoSeconds<u64>>")]
+    #[serde(rename = "cold_durations_ns", skip_serializing_if = "Vec::is_empty", default)]
+    pub cold_durations: Vec<Duration>,
+
+    /// Durations of the first iterations, left out of the statistics with `--discard-first`.
+    #[tabled(skip)]
+    #[serde_as(as = "Vec<DurationNanoSeconds<u64>>")]
+    #[serde(rename = "discarded_durations_ns", skip_serializing_if = "Vec::is_empty", default)]
+    pub discarded_durations: Vec<Duration>,
+
+    /// Confidence intervals of the mean and percentiles, estimated by bootstrapping.
+    #[tabled(rename = "CI95", display_with = "util::format_confidence")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub confidence: Option<ConfidenceIntervals>,
+
+    #[tabled(rename = "RoundSpread", display_with = "util::format_round_spread")]
+    #[serde_as(as = "Vec<DurationNanoSeconds<u64>>")]
+    #[serde(rename = "round_averages_ns", skip_serializing_if = "Vec::is_empty", default)]
+    pub round_averages: Vec<Duration>,
+
+    #[tabled(rename = "Noise", display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub noise: Option<RunNoise>,
+
+    #[tabled(rename = "IO", display_with = "util::format_option")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub io: Option<IoStats>,
+
+    #[tabled(rename = "Blocked", display_with = "util::format_blocked")]
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub waits: Vec<WaitEvent>,
+
+    /// Statements aborted by deadlocks and serialization failures, retried or not.
+    #[tabled(rename = "Aborts", display_with("util::format_aborts", self))]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub aborts: Option<Aborts>,
+
+    /// Plans of the query explained during the iterations with `--plan-check`.
+    #[tabled(rename = "Plan", display_with("util::format_plans", self))]
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub plans: Vec<PlanSample>,
+}
+
+// Define an enum to represent the outcome of a single query revision benchmark.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionStatus {
+    #[default]
+    Success,
+    Skipped,
+    TimedOut,
+    Failed,
+    Interrupted,
+    RowLimitExceeded,
+    SloViolated,
+}
+
+impl Display for RevisionStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RevisionStatus::Success => write!(f, "success"),
+            RevisionStatus::Skipped => write!(f, "skipped"),
+            RevisionStatus::TimedOut => write!(f, "timed out"),
+            RevisionStatus::Failed => write!(f, "failed"),
+            RevisionStatus::Interrupted => write!(f, "interrupted"),
+            RevisionStatus::RowLimitExceeded => write!(f, "row limit exceeded"),
+            RevisionStatus::SloViolated => write!(f, "slo violated"),
+        }
+    }
+}
+
+impl QueryRevisionResult {
+    /// Marks the result as timed out on the given zero based iteration.
+    pub fn mark_timed_out(&mut self, timeout: Duration, iteration: usize) {
+        self.status = RevisionStatus::TimedOut;
+        self.message = Some(format!(
+            "Query exceeded timeout of {} on iteration {}",
+            humantime::format_duration(timeout),
+            iteration + 1
+        ));
+    }
+
+    /// Marks the result as interrupted before completing the given zero based iteration.
+    pub fn mark_interrupted(&mut self, iteration: usize) {
+        self.status = RevisionStatus::Interrupted;
+        self.message = Some(format!("Run interrupted on iteration {}", iteration + 1));
+    }
+
+    /// Marks the result as interrupted by a guardrail aborting the run, described by `reason`.
+    pub fn mark_aborted(&mut self, reason: &str) {
+        self.status = RevisionStatus::Interrupted;
+        self.message = Some(format!("Run aborted: {}", reason));
+    }
+
+    /// Marks the result as violating its latency objectives, described by `violations`.
+    pub fn mark_slo_violated(&mut self, violations: Vec<String>) {
+        self.status = RevisionStatus::SloViolated;
+        self.message = Some(violations.join(", "));
+    }
+
+    /// Marks the result as failed for returning different rows than the reference revision.
+    pub fn mark_not_equivalent(&mut self, reference: &QueryRevisionResult) {
+        self.status = RevisionStatus::Failed;
+        self.message = Some(format!(
+            "Returned different rows than revision {}: {} rows with checksum {} instead of {} rows with checksum {}",
+            reference.revision_name,
+            util::format_option(&self.rows),
+            util::format_option(&self.checksum),
+            util::format_option(&reference.rows),
+            util::format_option(&reference.checksum)
+        ));
+    }
+
+    /// Marks the result as having returned more than `max_rows` rows on the given zero based
+    /// iteration.
+    pub fn mark_row_limit_exceeded(&mut self, max_rows: u64, iteration: usize) {
+        self.status = RevisionStatus::RowLimitExceeded;
+        self.message = Some(format!(
+            "Query returned more than {} rows on iteration {}, fetching stopped",
+            max_rows,
+            iteration + 1
+        ));
+    }
+
+    /// Returns how many times the plan explained with `--plan-check` changed during the
+    /// iterations, averaging their durations over different plans when non zero.
+    pub fn plan_changes(&self) -> usize {
+        self.plans.windows(2).filter(|pair| pair[0].hash != pair[1].hash).count()
+    }
+}
+
+// Define a struct to hold connection pool usage statistics of a run.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PoolStats {
+    pub max_connections: u32,
+    pub acquisitions: usize,
+
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "total_acquire_duration_ns")]
+    pub total_acquire_duration: Duration,
+
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "max_acquire_duration_ns")]
+    pub max_acquire_duration: Duration,
+
+    pub peak_in_use: u32,
+}
+
+impl PoolStats {
+    /// Records a connection acquisition that waited `waited`, with `in_use` connections
+    /// checked out of the pool afterwards.
+    pub fn record(&mut self, waited: Duration, in_use: u32) {
+        self.acquisitions += 1;
+        self.total_acquire_duration += waited;
+        self.max_acquire_duration = self.max_acquire_duration.max(waited);
+        self.peak_in_use = self.peak_in_use.max(in_use);
+    }
+
+    /// Returns true when every connection of the pool was in use at some point, meaning
+    /// benchmarks may have waited on qbench itself rather than the database.
+    pub fn is_saturated(&self) -> bool {
+        self.peak_in_use >= self.max_connections
+    }
+}
+
+impl Display for PoolStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let avg = match self.acquisitions {
+            0 => Duration::ZERO,
+            n => self.total_acquire_duration / n as u32,
+        };
+        write!(
+            f,
+            "Connection pool: {} acquisitions, avg wait {}, max wait {}, peak {} of {} connections in use",
+            self.acquisitions,
+            util::format_duration_pretty(&avg),
+            util::format_duration_pretty(&self.max_acquire_duration),
+            self.peak_in_use,
+            self.max_connections
+        )
+    }
+}
+
+// Define a struct to hold the blocks of user tables and their indexes read from disk, or found
+// in the buffer cache, while a revision ran.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStats {
+    pub heap_blks_read: u64,
+    pub heap_blks_hit: u64,
+    pub idx_blks_read: u64,
+    pub idx_blks_hit: u64,
+}
+
+impl IoStats {
+    /// Returns the blocks read and hit since the `before` snapshot of the counters, counters
+    /// reset in between counting from zero.
+    pub fn since(&self, before: &IoStats) -> IoStats {
+        IoStats {
+            heap_blks_read: self.heap_blks_read.saturating_sub(before.heap_blks_read),
+            heap_blks_hit: self.heap_blks_hit.saturating_sub(before.heap_blks_hit),
+            idx_blks_read: self.idx_blks_read.saturating_sub(before.idx_blks_read),
+            idx_blks_hit: self.idx_blks_hit.saturating_sub(before.idx_blks_hit),
+        }
+    }
+
+    /// Adds the blocks read and hit of another measurement, e.g. another round.
+    pub fn add(&mut self, other: &IoStats) {
+        self.heap_blks_read += other.heap_blks_read;
+        self.heap_blks_hit += other.heap_blks_hit;
+        self.idx_blks_read += other.idx_blks_read;
+        self.idx_blks_hit += other.idx_blks_hit;
+    }
+}
+
+impl Display for IoStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "heap {} read/{} hit, index {} read/{} hit",
+            self.heap_blks_read, self.heap_blks_hit, self.idx_blks_read, self.idx_blks_hit
+        )
+    }
+}
+
+// Define an enum to represent the concurrency failures the database aborts a statement with,
+// which are likely to succeed when retried.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConcurrencyFailure {
+    Deadlock,
+    Serialization,
+    /// The database was locked by another session, e.g. `SQLITE_BUSY`.
+    Busy,
+}
+
+// Define a struct to hold the concurrency failures of the iterations of a revision, whether they
+// were retried or failed the revision.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aborts {
+    pub deadlocks: usize,
+    pub serialization_failures: usize,
+    #[serde(default)]
+    pub busy: usize,
+
+    /// Time each retried iteration lost to its failed attempts and their backoff.
+    #[serde_as(as = "Vec<DurationNanoSeconds<u64>>")]
+    #[serde(rename = "retry_durations_ns", skip_serializing_if = "Vec::is_empty", default)]
+    pub retry_durations: Vec<Duration>,
+}
+
+impl Aborts {
+    /// Counts a statement aborted by a concurrency failure.
+    pub fn record(&mut self, failure: ConcurrencyFailure) {
+        match failure {
+            ConcurrencyFailure::Deadlock => self.deadlocks += 1,
+            ConcurrencyFailure::Serialization => self.serialization_failures += 1,
+            ConcurrencyFailure::Busy => self.busy += 1,
+        }
+    }
+
+    /// Returns the number of aborted statements.
+    pub fn total(&self) -> usize {
+        self.deadlocks + self.serialization_failures + self.busy
+    }
+
+    /// Adds the aborts of another measurement, e.g. another round.
+    pub fn add(&mut self, other: &Aborts) {
+        self.deadlocks += other.deadlocks;
+        self.serialization_failures += other.serialization_failures;
+        self.busy += other.busy;
+        self.retry_durations.extend(&other.retry_durations);
+    }
+}
+
+// Define a struct to hold the buffer cache hit ratio of the blocks a benchmark accessed, next to
+// the one of the database before it ran.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheHitRatio {
+    /// Hit ratio of the database before the benchmark, over the life of its counters.
+    pub before: f64,
+    /// Hit ratio of the blocks accessed while the benchmark ran.
+    pub during: f64,
+    /// Change of the hit ratio while the benchmark ran, `during - before`.
+    pub delta: f64,
+}
+
+impl CacheHitRatio {
+    /// Computes the hit ratios out of two snapshots of the `(blocks read, blocks hit)` counters
+    /// of the database, taken before and after the benchmark.
+    ///
+    /// Returns `None` when no block was accessed before or in between, or the counters were
+    /// reset in between.
+    pub fn between(before: (u64, u64), after: (u64, u64)) -> Option<CacheHitRatio> {
+        let ratio = |read: u64, hit: u64| match read + hit {
+            0 => None,
+            total => Some(hit as f64 / total as f64),
+        };
+        let before_ratio = ratio(before.0, before.1)?;
+        let during = ratio(after.0.checked_sub(before.0)?, after.1.checked_sub(before.1)?)?;
+        Some(CacheHitRatio {
+            before: before_ratio,
+            during,
+            delta: during - before_ratio,
+        })
+    }
+}
+
+impl Display for CacheHitRatio {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}% ({:+.1})", self.during * 100.0, self.delta * 100.0)
+    }
+}
+
+/// Confidence level of the intervals estimated for the mean and percentiles of revisions.
+pub const CONFIDENCE_LEVEL: f64 = 0.95;
+
+// Define a struct to hold the confidence intervals of the mean and percentiles of the latency
+// of a revision.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConfidenceIntervals {
+    /// Probability of the intervals to contain the true value, e.g. `0.95`.
+    pub level: f64,
+    pub mean: Interval,
+    pub p50: Interval,
+    pub p95: Interval,
+    pub p99: Interval,
+}
+
+// Define a struct to hold the bounds of a confidence interval of a latency.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Interval {
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "lower_ns")]
+    pub lower: Duration,
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "upper_ns")]
+    pub upper: Duration,
+}
+
+// Define a struct to hold how much the latency of a revision varies within a run, and from one
+// run to the next.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunNoise {
+    /// Standard deviation of the iterations of the run relative to their mean, in percent.
+    pub within: f64,
+    /// Standard deviation of the average latencies of the runs relative to their mean, in
+    /// percent, when there were several.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub between: Option<f64>,
+    /// Number of runs `between` was computed over, rounds of this run or runs of the history.
+    #[serde(default)]
+    pub runs: usize,
+}
+
+impl Display for RunNoise {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "±{:.1}% within", self.within)?;
+        if let Some(between) = self.between {
+            write!(f, ", ±{:.1}% between ({} runs)", between, self.runs)?;
+        }
+        Ok(())
+    }
+}
+
+// Define a struct to hold the sizes of the tables a benchmark references, before and after it ran.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TableSizes {
+    pub before: Vec<TableSize>,
+    pub after: Vec<TableSize>,
+}
+
+// Define a struct to hold the size of a table and its indexes at one point of the run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableSize {
+    pub table: String,
+    pub table_bytes: u64,
+    pub index_bytes: u64,
+    /// Number of rows estimated by the database, `None` if it never estimated it.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rows_estimate: Option<u64>,
+}
+
+// Define a struct to hold the time the session of a revision spent waiting on one event, e.g. a
+// lock on a relation, while it ran.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WaitEvent {
+    /// Type of the event, e.g. `Lock` or `IO`.
+    pub event_type: String,
+    /// The event, e.g. `relation` or `DataFileRead`.
+    pub event: String,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub lock_type: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub relation: Option<String>,
+
+    #[serde_as(as = "DurationNanoSeconds<u64>")]
+    #[serde(rename = "duration_ns")]
+    pub duration: Duration,
+}
+
+impl WaitEvent {
+    /// Returns true when the event is the same as another one, whatever the time spent on each.
+    pub fn is_same(&self, other: &WaitEvent) -> bool {
+        (&self.event_type, &self.event, &self.lock_type, &self.relation)
+            == (&other.event_type, &other.event, &other.lock_type, &other.relation)
+    }
+
+    /// Returns true when the session was blocked by another one rather than, e.g., waiting on I/O.
+    pub fn is_blocked(&self) -> bool {
+        self.event_type == "Lock"
+    }
+}
+
+// Define a struct to hold the plan of the query of a revision, explained before an iteration.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlanSample {
+    /// The zero based iteration the plan was explained before.
+    pub iteration: usize,
+    /// Hash of the plan, the same for the same plan whatever its estimated costs.
+    pub hash: String,
 }
 
 // Define a struct to hold multiple QueryBench instances.
 #[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct QueryBenches {
+    pub setup: Option<SuiteScript>,
+    pub teardown: Option<SuiteScript>,
+    #[serde(default)]
+    pub queries: Vec<QueryBench>,
+    #[serde(default)]
+    pub suites: Vec<BenchSuite>,
+}
+
+// Define a struct to hold a named group of benchmarks sharing their setup, teardown and tags.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BenchSuite {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub setup: Option<SuiteScript>,
+    pub teardown: Option<SuiteScript>,
     pub queries: Vec<QueryBench>,
 }
 
+// Define a struct to hold a script executed once before or after all benchmarks of a file.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteScript {
+    pub script: String,
+}
+
 // Define a struct to hold a single query benchmark, including multiple revisions.
 #[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct QueryBench {
     pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub enabled: Option<bool>,
+    pub disabled_reason: Option<String>,
+    pub iterations: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    pub cold_connection: Option<bool>,
+    pub reset_session: Option<bool>,
+    pub autocommit: Option<bool>,
+    pub commit: Option<bool>,
+    pub isolate: Option<bool>,
+    pub protocol: Option<QueryProtocol>,
+    pub fetch: Option<bool>,
+    pub fetch_size: Option<NonZeroUsize>,
+    pub verify_equivalence: Option<bool>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_avg: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_p50: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_p95: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_p99: Option<Duration>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    pub pre_script_each_iteration: Option<String>,
+    pub post_script_each_iteration: Option<String>,
+    pub think_time: Option<ThinkTime>,
+    #[serde(default)]
+    pub fixtures: Vec<String>,
+    pub maintenance: Option<Maintenance>,
+    pub pool: Option<PoolSettings>,
+    #[serde(default)]
+    pub target: Target,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub tables: Vec<String>,
+    /// Name of the suite the benchmark belongs to, if any, set when the config is loaded.
+    #[serde(skip)]
+    pub suite: Option<String>,
+    #[serde(default)]
+    pub serial: bool,
     pub revisions: Vec<QueryRevision>,
 }
 
+// Define a struct to hold the connection pool settings a benchmark overrides.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PoolSettings {
+    pub max_connections: Option<u32>,
+    #[serde(default, with = "humantime_serde")]
+    pub acquire_timeout: Option<Duration>,
+}
+
+// Define an enum for the database a benchmark runs against.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    /// The database of `--url`, which the setup and teardown scripts run against too.
+    #[default]
+    Primary,
+    /// The read replica of `--replica-url`.
+    Replica,
+}
+
+impl QueryBench {
+    /// Returns false only when the benchmark is explicitly disabled with `enabled = false`.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    /// Returns the tables the benchmark references: the ones it lists in `tables`, or else the
+    /// candidates found in the queries of its revisions.
+    pub fn referenced_tables(&self) -> Vec<String> {
+        if !self.tables.is_empty() {
+            return self.tables.clone();
+        }
+        let mut tables: Vec<String> = vec![];
+        for revision in &self.revisions {
+            for table in util::referenced_tables(&revision.query) {
+                if !tables.contains(&table) {
+                    tables.push(table);
+                }
+            }
+        }
+        tables
+    }
+
+    /// Returns a copy of the given revision with settings it doesn't override itself
+    /// inherited from this benchmark.
+    pub fn effective_revision(&self, revision: &QueryRevision) -> QueryRevision {
+        QueryRevision {
+            iterations: revision.iterations.or(self.iterations),
+            timeout: revision.timeout.or(self.timeout),
+            cold_connection: revision.cold_connection.or(self.cold_connection),
+            reset_session: revision.reset_session.or(self.reset_session),
+            autocommit: revision.autocommit.or(self.autocommit),
+            commit: revision.commit.or(self.commit),
+            isolate: revision.isolate.or(self.isolate),
+            protocol: revision.protocol.or(self.protocol),
+            fetch: revision.fetch.or(self.fetch),
+            fetch_size: revision.fetch_size.or(self.fetch_size),
+            verify_equivalence: revision.verify_equivalence.or(self.verify_equivalence),
+            max_avg: revision.max_avg.or(self.max_avg),
+            max_p50: revision.max_p50.or(self.max_p50),
+            max_p95: revision.max_p95.or(self.max_p95),
+            max_p99: revision.max_p99.or(self.max_p99),
+            settings: self
+                .settings
+                .clone()
+                .into_iter()
+                .chain(revision.settings.clone())
+                .collect(),
+            pre_script_each_iteration: revision
+                .pre_script_each_iteration
+                .clone()
+                .or_else(|| self.pre_script_each_iteration.clone()),
+            post_script_each_iteration: revision
+                .post_script_each_iteration
+                .clone()
+                .or_else(|| self.post_script_each_iteration.clone()),
+            think_time: revision.think_time.or(self.think_time),
+            fixtures: match revision.fixtures.is_empty() {
+                true => self.fixtures.clone(),
+                false => revision.fixtures.clone(),
+            },
+            maintenance: revision.maintenance.or(self.maintenance),
+            ..revision.clone()
+        }
+    }
+}
+
+// Define a struct to hold the latency objectives the durations of a revision must meet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencySlo {
+    pub max_avg: Option<Duration>,
+    pub max_p50: Option<Duration>,
+    pub max_p95: Option<Duration>,
+    pub max_p99: Option<Duration>,
+}
+
+impl LatencySlo {
+    /// Describes the objectives the given durations don't meet, empty when all are met.
+    ///
+    /// # Arguments
+    ///
+    /// * `durations` - The measured durations of a revision.
+    pub fn violations(&self, durations: &[Duration]) -> Vec<String> {
+        if durations.is_empty() {
+            return vec![];
+        }
+        let measured = [
+            ("avg", self.max_avg, util::average_duration(durations)),
+            ("p50", self.max_p50, util::percentile(durations, 50.0)),
+            ("p95", self.max_p95, util::percentile(durations, 95.0)),
+            ("p99", self.max_p99, util::percentile(durations, 99.0)),
+        ];
+        measured
+            .into_iter()
+            .filter_map(|(name, max, actual)| match max {
+                Some(max) if actual > max => Some(format!(
+                    "{} of {} exceeds {}",
+                    name,
+                    humantime::format_duration(actual),
+                    humantime::format_duration(max)
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
 // Define a struct to hold the details of a single query revision benchmark.
 #[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct QueryRevision {
     pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub enabled: Option<bool>,
+    pub disabled_reason: Option<String>,
+    pub iterations: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    pub cold_connection: Option<bool>,
+    pub reset_session: Option<bool>,
+    pub autocommit: Option<bool>,
+    pub commit: Option<bool>,
+    pub isolate: Option<bool>,
+    pub protocol: Option<QueryProtocol>,
+    pub fetch: Option<bool>,
+    pub fetch_size: Option<NonZeroUsize>,
+    pub verify_equivalence: Option<bool>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_avg: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_p50: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_p95: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_p99: Option<Duration>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
     pub query: String,
+    pub expect_rows: Option<u64>,
+    pub expect_checksum: Option<String>,
+    #[serde(default)]
+    pub fixtures: Vec<String>,
+    pub maintenance: Option<Maintenance>,
     pub pre_script: Option<String>,
     pub post_script: Option<String>,
+    pub pre_script_each_iteration: Option<String>,
+    pub post_script_each_iteration: Option<String>,
+    pub think_time: Option<ThinkTime>,
 }
 
-// Define a trait for parsing query benchmarks.
+impl QueryRevision {
+    /// Returns false only when the revision is explicitly disabled with `enabled = false`.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    /// Returns the latency objectives declared by the revision.
+    pub fn slo(&self) -> LatencySlo {
+        LatencySlo {
+            max_avg: self.max_avg,
+            max_p50: self.max_p50,
+            max_p95: self.max_p95,
+            max_p99: self.max_p99,
+        }
+    }
+
+    /// Returns true when the revision declares expectations on the rows its query returns.
+    pub fn has_expectations(&self) -> bool {
+        self.expect_rows.is_some() || self.expect_checksum.is_some()
+    }
+
+    /// Checks the rows returned by the query against the expectations of the revision.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Number of rows the query returned.
+    /// * `checksum` - Checksum of the rows, computed when `expect_checksum` is set.
+    pub fn verify_expectations(&self, rows: Option<u64>, checksum: Option<&str>) -> Result<()> {
+        if let Some(expected) = self.expect_rows {
+            if rows != Some(expected) {
+                return Err(anyhow!(
+                    "Revision {} returned {} rows, expected {}",
+                    self.name,
+                    util::format_option(&rows),
+                    expected
+                ));
+            }
+        }
+        if let Some(expected) = &self.expect_checksum {
+            if checksum != Some(expected.as_str()) {
+                return Err(anyhow!(
+                    "Revision {} returned rows with checksum {}, expected {}",
+                    self.name,
+                    checksum.unwrap_or_default(),
+                    expected
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a benchmark config file, implemented with `#[async_trait]` by the parsers of
+/// additional formats registered with [`parser::DefaultParser::register`].
 #[async_trait::async_trait]
-trait QueryBenchParser {
+pub trait QueryBenchParser: Send + Sync {
+    /// Parses the benchmarks of the file at `path`.
     async fn parse(&self, path: &Path) -> Result<QueryBenches>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_an_explicit_enabled_false_disables() {
+        let mut bench = QueryBench::default();
+        let mut revision = QueryRevision::default();
+        assert!(bench.is_enabled() && revision.is_enabled());
+        bench.enabled = Some(true);
+        revision.enabled = Some(true);
+        assert!(bench.is_enabled() && revision.is_enabled());
+        bench.enabled = Some(false);
+        revision.enabled = Some(false);
+        assert!(!bench.is_enabled() && !revision.is_enabled());
+    }
+
+    #[test]
+    fn latency_slo_reports_the_objectives_exceeded() {
+        let durations: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        let slo = LatencySlo {
+            max_avg: Some(Duration::from_millis(60)),
+            max_p50: Some(Duration::from_millis(40)),
+            max_p95: Some(Duration::from_millis(90)),
+            max_p99: None,
+        };
+        let violations = slo.violations(&durations);
+        assert_eq!(violations.len(), 2, "{:?}", violations);
+        assert!(violations[0].starts_with("p50 of "));
+        assert!(violations[0].ends_with(" exceeds 40ms"));
+        assert!(violations[1].starts_with("p95 of "));
+        assert!(violations[1].ends_with(" exceeds 90ms"));
+        assert!(slo.violations(&[]).is_empty());
+        assert!(LatencySlo::default().violations(&durations).is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::args::GroupBy;
+use crate::schedule::Drift;
+use crate::util::{self, format_duration_short, percentile};
+use crate::QueryBenchResult;
+
+/// Renders the results of a run in Markdown: a table of the revisions of each benchmark with the
+/// sentences comparing them, the regressions since the previous run, and the overview of the run.
+///
+/// # Arguments
+///
+/// * `bench_res` - The results of the benchmarks.
+/// * `regressions` - The revisions that got slower since the previous run.
+/// * `summary` - The overview of the run, as printed after its results.
+/// * `group_by` - What the benchmarks are grouped by, each group with its own summary, if any.
+pub fn report(
+    bench_res: &[QueryBenchResult],
+    regressions: &[Drift],
+    summary: &str,
+    group_by: Option<GroupBy>,
+) -> String {
+    let mut out = String::from("## qbench results\n\n");
+
+    if !regressions.is_empty() {
+        out += "> [!WARNING]\n> Regressions since the previous run:\n";
+        for r in regressions {
+            out += &format!(
+                "> - **{}/{}** {:+.1}% ({:.3}ms → {:.3}ms)\n",
+                escape(&r.bench),
+                escape(&r.revision),
+                r.change_pct,
+                r.before_ms,
+                r.after_ms
+            );
+        }
+        out += "\n";
+    }
+
+    match group_by {
+        Some(group_by) => {
+            for group in util::group_results(bench_res, group_by) {
+                out += &format!("### {}\n\n", escape(&group.name));
+                for line in util::format_group_summary(&group) {
+                    out += &format!("{}\n\n", escape(&line));
+                }
+                for bench in &group.benches {
+                    out += &bench_section(bench, "####");
+                }
+            }
+        }
+        None => {
+            for bench in bench_res {
+                out += &bench_section(bench, "###");
+            }
+        }
+    }
+
+    let suite = util::format_geometric_means("suite", bench_res);
+    if !suite.is_empty() {
+        out += "### Suite\n\n";
+        for line in suite {
+            out += &format!("{}\n\n", escape(&line));
+        }
+    }
+    for line in summary.lines() {
+        out += &format!("{}\n\n", escape(line));
+    }
+    out
+}
+
+/// Renders the table of the revisions of a benchmark under a heading of the given level, e.g.
+/// `###`, followed by the sentences comparing them.
+fn bench_section(bench: &QueryBenchResult, heading: &str) -> String {
+    let mut out = format!("{} {}\n\n", heading, escape(&bench.name));
+    out += "| Revision | Status | Iterations | Average | p50 | p95 | p99 | Mean 95% CI |\n";
+    out += "|---|---|--:|--:|--:|--:|--:|--:|\n";
+    for revision in &bench.results {
+        let stats = match revision.durations.is_empty() {
+            true => vec!["-".to_string(); 4],
+            false => {
+                let mut stats = vec![format_duration_short(&revision.avg_query_duration)];
+                stats.extend([50.0, 95.0, 99.0].map(|p| {
+                    format_duration_short(&percentile(&revision.durations, p))
+                }));
+                stats
+            }
+        };
+        let interval = revision.confidence.map_or("-".to_string(), |c| {
+            format!(
+                "{} – {}",
+                format_duration_short(&c.mean.lower),
+                format_duration_short(&c.mean.upper)
+            )
+        });
+        out += &format!(
+            "| {} | {} | {} | {} | {} |\n",
+            escape(&revision.revision_name),
+            revision.status,
+            revision.durations.len(),
+            stats.join(" | "),
+            interval
+        );
+    }
+    out += "\n";
+    for speedup in util::format_speedups(bench) {
+        out += &format!("{}\n\n", escape(&speedup));
+    }
+    out
+}
+
+/// Appends Markdown to a file, such as the step summary of a GitHub Actions job.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file, created if needed.
+/// * `markdown` - The Markdown to append.
+pub fn append(path: &Path, markdown: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Error opening {}", path.display()))?;
+    file.write_all(markdown.as_bytes())
+        .with_context(|| format!("Error writing {}", path.display()))
+}
+
+/// Escapes the characters of a name that Markdown would interpret in a table or a paragraph.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '|' | '*' | '_' | '`' | '<' | '>' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
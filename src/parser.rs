@@ -1,4 +1,7 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
 use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -6,36 +9,88 @@ use tokio::fs::read_to_string;
 
 use crate::{QueryBenches, QueryBenchParser};
 
-pub struct DefaultParser {}
+/// Parses config files by their extension: JSON and TOML out of the box, plus the formats of
+/// the parsers registered with [`DefaultParser::register`].
+#[derive(Clone, Default)]
+pub struct DefaultParser {
+    /// Parsers of additional formats, by lowercase file extension.
+    parsers: BTreeMap<String, Arc<dyn QueryBenchParser>>,
+}
 
 impl DefaultParser {
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Registers a parser for the files with the given extension, taking precedence over the
+    /// built-in JSON and TOML parsers and over a parser registered before for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - Extension of the files, e.g. `yaml`, matched case insensitively.
+    /// * `parser` - The parser of the files.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn example() -> anyhow::Result<()> {
+    /// use std::path::Path;
+    ///
+    /// use qbench::parser::DefaultParser;
+    /// use qbench::{QueryBenchParser, QueryBenches};
+    ///
+    /// struct YamlParser;
+    ///
+    /// #[async_trait::async_trait]
+    /// impl QueryBenchParser for YamlParser {
+    ///     async fn parse(&self, path: &Path) -> anyhow::Result<QueryBenches> {
+    ///         todo!("parse {}", path.display())
+    ///     }
+    /// }
+    ///
+    /// let mut parser = DefaultParser::new();
+    /// parser.register("yaml", YamlParser);
+    /// let benches = parser.parse(Path::new("benches/users.yaml")).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register(&mut self, extension: &str, parser: impl QueryBenchParser + 'static) {
+        let extension = extension.trim_start_matches('.').to_lowercase();
+        self.parsers.insert(extension, Arc::new(parser));
+    }
+}
+
+impl Debug for DefaultParser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DefaultParser")
+            .field("extensions", &self.parsers.keys().collect::<Vec<_>>())
+            .finish()
     }
 }
 
 #[async_trait]
 impl QueryBenchParser for DefaultParser {
     async fn parse(&self, path: &Path) -> Result<QueryBenches> {
+        let Some(ext) = path.extension() else {
+            return Err(anyhow!("File has no extension, cannot determine parser"));
+        };
+        let ext = ext.to_string_lossy().to_lowercase();
+        if let Some(parser) = self.parsers.get(&ext) {
+            return parser.parse(path).await;
+        }
+
         let file_content = read_to_string(path).await?;
-        match path.extension() {
-            Some(ext) => match ext.to_str() {
-                Some("json") => {
-                    let qb: QueryBenches = serde_json::from_str(file_content.as_str())?;
-                    Ok(qb)
-                }
-                Some("toml") => {
-                    let qb: QueryBenches = toml::from_str(file_content.as_str())?;
-                    Ok(qb)
-                }
-                _ => return Err(anyhow!("Unsupported file extension: {}", path.display())),
-            },
-            _ => {
-                return Err(anyhow!(
-                    "File has no extension, cannot determine parser: {}",
-                    path.display()
-                ));
+        // Both errors point at the line and column of the problem, e.g. a misspelled key
+        match ext.as_str() {
+            "json" => {
+                let qb: QueryBenches = serde_json::from_str(file_content.as_str())?;
+                Ok(qb)
             }
+            "toml" => {
+                let qb: QueryBenches = toml::from_str(file_content.as_str())?;
+                Ok(qb)
+            }
+            _ => Err(anyhow!("Unsupported file extension: {}", ext)),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+use crate::util::format_duration_pretty;
+
+/// What a run would execute, resolved from the config files and the command line by
+/// [`crate::bench::QBench::plan`] without connecting to the database.
+#[derive(Debug, Clone, Default)]
+pub struct RunPlan {
+    /// Migrations, run once before everything else.
+    pub migrations: Vec<String>,
+    /// Setup scripts, in the order they run.
+    pub setups: Vec<String>,
+    /// Teardown scripts, in the order they run.
+    pub teardowns: Vec<String>,
+    /// Number of times the benchmarks run, one round after the other.
+    pub rounds: usize,
+    /// The benchmarks selected by the filters of the command line, in file order.
+    pub benches: Vec<PlannedBench>,
+    /// The other databases the suite runs against with `--target`, redacted.
+    pub targets: Vec<String>,
+}
+
+/// A benchmark of a [`RunPlan`].
+#[derive(Debug, Clone, Default)]
+pub struct PlannedBench {
+    pub name: String,
+    pub suite: Option<String>,
+    /// The database the benchmark runs against, redacted.
+    pub database: String,
+    pub depends_on: Vec<String>,
+    pub revisions: Vec<PlannedRevision>,
+}
+
+/// A revision of a [`PlannedBench`], with the settings it inherits from its benchmark.
+#[derive(Debug, Clone, Default)]
+pub struct PlannedRevision {
+    pub name: String,
+    /// Why the revision is skipped, when it or its benchmark is disabled.
+    pub skipped: Option<String>,
+    /// Timed iterations per round, down to the share of this process with `--shard`.
+    pub iterations: usize,
+    /// The statements of the query, in the order they are sent.
+    pub statements: Vec<String>,
+    pub pre_script: Option<String>,
+    pub post_script: Option<String>,
+    pub pre_script_each_iteration: Option<String>,
+    pub post_script_each_iteration: Option<String>,
+    pub fixtures: Vec<String>,
+    pub settings: BTreeMap<String, String>,
+}
+
+impl RunPlan {
+    /// Returns the number of revisions that would run and of the iterations they would time.
+    pub fn totals(&self) -> (usize, usize) {
+        let running = self
+            .benches
+            .iter()
+            .flat_map(|bench| &bench.revisions)
+            .filter(|revision| revision.skipped.is_none());
+        running.fold((0, 0), |(revisions, iterations), revision| {
+            (revisions + 1, iterations + revision.iterations * self.rounds)
+        })
+    }
+}
+
+impl Display for RunPlan {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for migration in &self.migrations {
+            write_script(f, "", "Migration", migration)?;
+        }
+        for setup in &self.setups {
+            write_script(f, "", "Setup", setup)?;
+        }
+
+        for bench in &self.benches {
+            write!(f, "Benchmark {} on {}", bench.name, bench.database)?;
+            if let Some(suite) = &bench.suite {
+                write!(f, ", suite {}", suite)?;
+            }
+            if !bench.depends_on.is_empty() {
+                write!(f, ", after {}", bench.depends_on.join(", "))?;
+            }
+            writeln!(f)?;
+            for revision in &bench.revisions {
+                if let Some(reason) = &revision.skipped {
+                    writeln!(f, "  Revision {}: skipped, {}", revision.name, reason)?;
+                    continue;
+                }
+                write!(f, "  Revision {}: {} iterations", revision.name, revision.iterations)?;
+                if self.rounds > 1 {
+                    write!(f, " × {} rounds", self.rounds)?;
+                }
+                writeln!(f)?;
+                for (name, value) in &revision.settings {
+                    writeln!(f, "    Setting {} = {}", name, value)?;
+                }
+                for fixture in &revision.fixtures {
+                    writeln!(f, "    Fixture {}", fixture)?;
+                }
+                let scripts = [
+                    ("Pre-script", &revision.pre_script),
+                    ("Pre-script of each iteration", &revision.pre_script_each_iteration),
+                ];
+                for (name, script) in scripts {
+                    if let Some(script) = script {
+                        write_script(f, "    ", name, script)?;
+                    }
+                }
+                writeln!(f, "    Query:")?;
+                for statement in &revision.statements {
+                    write_indented(f, "      ", statement)?;
+                }
+                let scripts = [
+                    ("Post-script of each iteration", &revision.post_script_each_iteration),
+                    ("Post-script", &revision.post_script),
+                ];
+                for (name, script) in scripts {
+                    if let Some(script) = script {
+                        write_script(f, "    ", name, script)?;
+                    }
+                }
+            }
+        }
+
+        for teardown in &self.teardowns {
+            write_script(f, "", "Teardown", teardown)?;
+        }
+        if !self.targets.is_empty() {
+            writeln!(f, "Then again on {}", self.targets.join(", "))?;
+        }
+        let (revisions, iterations) = self.totals();
+        write!(
+            f,
+            "{} benchmarks, {} revisions, {} timed iterations.",
+            self.benches.len(),
+            revisions,
+            iterations
+        )
+    }
+}
+
+/// How long a run is expected to take, extrapolated by [`crate::bench::QBench::estimate`] from
+/// one probe iteration of each revision.
+#[derive(Debug, Clone, Default)]
+pub struct RunEstimate {
+    /// Expected duration of the run, all rounds included.
+    pub total: Duration,
+    /// How long the probe took.
+    pub probe: Duration,
+    /// Expected duration of each benchmark in a round, in file order.
+    pub benches: Vec<(String, Duration)>,
+    /// The revisions whose probe failed, as `benchmark/revision`, left out of the estimate.
+    pub failed: Vec<String>,
+}
+
+impl Display for RunEstimate {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if !self.benches.is_empty() {
+            writeln!(f, "Estimated duration of each benchmark, per round:")?;
+        }
+        for (name, duration) in &self.benches {
+            writeln!(f, "  {}: {}", name, format_duration_pretty(duration))?;
+        }
+        if !self.failed.is_empty() {
+            writeln!(f, "Left out, their probe failed: {}", self.failed.join(", "))?;
+        }
+        write!(
+            f,
+            "Estimated duration of the run: {} (probed in {})",
+            format_duration_pretty(&self.total),
+            format_duration_pretty(&self.probe)
+        )
+    }
+}
+
+/// Writes a script under a heading, its lines indented below it.
+fn write_script(f: &mut Formatter, indent: &str, heading: &str, script: &str) -> fmt::Result {
+    writeln!(f, "{}{}:", indent, heading)?;
+    write_indented(f, &format!("{}  ", indent), script.trim())
+}
+
+/// Writes each line of a text with the given indentation.
+fn write_indented(f: &mut Formatter, indent: &str, text: &str) -> fmt::Result {
+    for line in text.lines() {
+        writeln!(f, "{}{}", indent, line)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use tracing_subscriber::fmt::MakeWriter;
+use url::Url;
+
+use crate::args::Args;
+
+/// What redacted text is replaced with, like the passwords of redacted URLs.
+const MASK: &str = "***";
+
+/// Secrets shorter than this aren't redacted, hiding every occurrence of a one or two letter
+/// password would garble the logs without hiding anything worth it.
+const MIN_SECRET_LEN: usize = 4;
+
+/// Hides sensitive text from logs, error messages and exports: the passwords and tokens given
+/// on the command line, wherever they show up, and the text matching the `--redact` patterns,
+/// such as customer ids or emails in the literals of queries.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Creates a redactor of the `--redact` patterns and the secrets of the command line: the
+    /// passwords of `--url` and `--replica-url`, the one of `--password-file` or
+    /// `--ask-password` if already read, and the token of `--endpoint-token`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The command line.
+    pub fn new(args: &Args) -> Self {
+        let passwords = [Some(&args.url), args.replica_url.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|url| Url::parse(url).ok())
+            .filter_map(|url| {
+                let password = percent_decode_str(url.password()?).decode_utf8().ok()?;
+                Some(password.into_owned())
+            });
+        let secrets = passwords
+            .chain(args.password.clone())
+            .chain(args.endpoint_token.clone())
+            .filter(|secret| secret.len() >= MIN_SECRET_LEN);
+
+        let mut patterns = args.redact.clone();
+        for secret in secrets {
+            // The JSON log escapes quotes and backslashes, hide the secret in that form too
+            let escaped = serde_json::to_string(&secret).expect("strings serialize");
+            let escaped = &escaped[1..escaped.len() - 1];
+            let mut forms = vec![regex::escape(&secret)];
+            if escaped != secret {
+                forms.push(regex::escape(escaped));
+            }
+            for form in forms {
+                patterns.push(Regex::new(&form).expect("escaped text is a valid regex"));
+            }
+        }
+        Redactor { patterns }
+    }
+
+    /// Returns whether the redactor hides nothing.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Replaces the sensitive parts of a text by `***`.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text, e.g. a statement or an error message.
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, MASK) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+}
+
+/// Makes writers redacting what the log writes through them.
+///
+/// Each event is written in a single call, so the text matching a pattern is never split
+/// between two writes.
+pub struct RedactingMakeWriter<M> {
+    inner: M,
+    redactor: Arc<Redactor>,
+}
+
+impl<M> RedactingMakeWriter<M> {
+    /// Wraps the writers made by `inner` to redact the text written through them.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Makes the writers the redacted text is written to.
+    /// * `redactor` - What to redact.
+    pub fn new(inner: M, redactor: Arc<Redactor>) -> Self {
+        RedactingMakeWriter { inner, redactor }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer(),
+            redactor: self.redactor.clone(),
+        }
+    }
+}
+
+/// A writer redacting the text written through it.
+pub struct RedactingWriter<W> {
+    inner: W,
+    redactor: Arc<Redactor>,
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.inner.write_all(self.redactor.redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn hides_the_passwords_given_apart_and_their_json_form() {
+        let mut args = Args::parse_from(["qbench", "-u", "postgres://user@localhost/db"]);
+        args.password = Some(r#"se"cr\et"#.to_string());
+        let redactor = Redactor::new(&args);
+        assert_eq!(redactor.redact(r#"password se"cr\et"#), "password ***");
+        let logged = r#"{"message":"password se\"cr\\et"}"#;
+        assert_eq!(redactor.redact(logged), r#"{"message":"password ***"}"#);
+    }
+}
@@ -0,0 +1,274 @@
+use std::env;
+use std::path::Path;
+use std::process::{self, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use url::Url;
+
+use crate::args::ExportFormat;
+use crate::bench::QBench;
+use crate::{util, QueryBenchResult};
+
+/// Options whose value is a path or endpoint of this host, or a credential. They are left out of
+/// the arguments of the remote run, which gets copies of what it needs instead.
+const LOCAL_OPTIONS: &[&str] = &[
+    "--remote",
+    "--remote-bin",
+    "-d",
+    "--bench-dir",
+    "-e",
+    "--export",
+    "-o",
+    "--out-file",
+    "--out-dir",
+    "--endpoint",
+    "--endpoint-header",
+    "--endpoint-token",
+    "--upload",
+    "--upload-key",
+    "--upload-endpoint",
+    "--history",
+    "--run-id",
+    "--fail-on",
+    "--gha-summary",
+    "--notify-url",
+    "--log-file",
+    "--otlp-endpoint",
+    "--setup-file",
+    "--teardown-file",
+    "-u",
+    "--url",
+    "--replica-url",
+    "--password-file",
+];
+
+/// Options without a value that are left out of the arguments of the remote run.
+const LOCAL_FLAGS: &[&str] = &["--ask-password"];
+
+/// Runs the benchmarks on a remote host over SSH and returns their results, so latencies are
+/// measured close to the database rather than across the network of the operator.
+///
+/// The bench directory and the setup and teardown files are copied to a temporary directory
+/// on the host, where the installed qbench runs with the other arguments of this one. Its
+/// output is shown as it goes and, when the terminal allows it, Ctrl-C is forwarded so an
+/// interrupted run still returns partial results. The pool statistics and interruption of the
+/// remote run are reported as the ones of `qbench`.
+///
+/// # Arguments
+///
+/// * `qbench` - The instance configured by the command line.
+/// * `host` - The host, as given to `ssh`, e.g. `user@bastion`.
+/// * `argv` - The command line arguments of this process, without the program name.
+pub async fn run(qbench: &QBench, host: &str, argv: &[String]) -> Result<Vec<QueryBenchResult>> {
+    if qbench.args.checkpoint.is_some() || qbench.args.resume.is_some() {
+        bail!("--checkpoint and --resume can't be combined with --remote");
+    }
+
+    let dir = ssh_output(host, "mktemp -d").await?;
+    let results = run_in(qbench, host, &dir, argv).await;
+    // Clean up even when the run failed, a failing clean up doesn't hide the outcome
+    let _ = ssh_output(host, &format!("rm -rf {}", quote(&dir))).await;
+    results
+}
+
+/// Runs the benchmarks in a temporary directory of the remote host.
+///
+/// # Arguments
+///
+/// * `qbench` - The instance configured by the command line.
+/// * `host` - The host, as given to `ssh`.
+/// * `dir` - The temporary directory on the host.
+/// * `argv` - The command line arguments of this process, without the program name.
+async fn run_in(
+    qbench: &QBench,
+    host: &str,
+    dir: &str,
+    argv: &[String],
+) -> Result<Vec<QueryBenchResult>> {
+    let args = &qbench.args;
+    scp(&args.dir, &format!("{}:{}/bench", host, dir), true).await?;
+
+    let mut remote_args = vec![quote(&args.remote_bin)];
+    remote_args.extend(forwarded_args(argv).iter().map(|arg| quote(arg)));
+    for (option, file, name) in [
+        ("--setup-file", &args.setup_file, "setup.sql"),
+        ("--teardown-file", &args.teardown_file, "teardown.sql"),
+    ] {
+        if let Some(file) = file {
+            scp(file, &format!("{}:{}/{}", host, dir, name), false).await?;
+            remote_args.extend([option.to_string(), name.to_string()]);
+        }
+    }
+    // Keep the password off the command line of the host, where other users could see it
+    let mut url = Url::parse(&args.url).ok();
+    if let Some(url) = url.as_mut().filter(|url| url.password().is_some()) {
+        let _ = url.set_password(None);
+    }
+    let url = url.map_or_else(|| args.url.clone(), String::from);
+    remote_args.extend(["--url".to_string(), quote(&url)]);
+    if let Some(password) = util::password(args, &args.url) {
+        write_file(host, &format!("{}/password", dir), &password).await?;
+        remote_args.extend(["--password-file", "password"].map(String::from));
+    }
+    // The queries on the host are commented with the id of this run
+    remote_args.extend(["--run-id".to_string(), quote(qbench.run_id())]);
+    // The paths are relative, qbench lowercases the path of the results file
+    remote_args.extend(["-d", "bench", "-e", "json", "-o", "results"].map(String::from));
+
+    // The replica's URL may hold a password too, so it goes through a file and the environment
+    let mut env = String::new();
+    if let Some(replica_url) = &args.replica_url {
+        write_file(host, &format!("{}/replica-url", dir), replica_url).await?;
+        env.push_str("QBENCH_REPLICA_URL=\"$(cat replica-url)\" ");
+    }
+
+    let command = format!("cd {} && {}{}", quote(dir), env, remote_args.join(" "));
+    let mut ssh = Command::new("ssh");
+    // A terminal on the host forwards Ctrl-C to the run instead of killing the connection
+    if console::user_attended() {
+        ssh.arg("-t");
+    }
+    // The run reports a failure with its output and exit status, told apart below by the
+    // results file missing
+    ssh.arg(host).arg(command).status().await.context("Error running ssh")?;
+
+    let local = env::temp_dir().join(format!("qbench-remote-{}.json", process::id()));
+    scp(Path::new(&format!("{}:{}/results.json", host, dir)), &local.to_string_lossy(), false)
+        .await
+        .map_err(|e| e.context("The remote run produced no results, see its output above"))?;
+    let content = fs::read_to_string(&local).await;
+    let _ = fs::remove_file(&local).await;
+    let results = util::parse_results(&content?, ExportFormat::Json)
+        .with_context(|| format!("Invalid results file {}:{}/results.json", host, dir))?;
+
+    if results.partial {
+        qbench.cancellation_token().cancel();
+    }
+    qbench.set_pool_stats(results.pool);
+    qbench.set_server_samples(results.server_samples);
+    Ok(results.exported)
+}
+
+/// Returns the arguments of this process the remote run is given, the ones with a value local
+/// to this host left out.
+///
+/// # Arguments
+///
+/// * `argv` - The command line arguments, without the program name.
+fn forwarded_args(argv: &[String]) -> Vec<String> {
+    let mut forwarded = vec![];
+    let mut argv = argv.iter();
+    while let Some(arg) = argv.next() {
+        if LOCAL_FLAGS.contains(&arg.as_str()) {
+            continue;
+        }
+        if LOCAL_OPTIONS.contains(&arg.as_str()) {
+            argv.next();
+            continue;
+        }
+        let local = LOCAL_OPTIONS.iter().any(|option| {
+            // `--option=value`, or `-ovalue` for short options
+            match option.strip_prefix("--") {
+                Some(_) => arg.starts_with(&format!("{}=", option)),
+                None => arg.starts_with(option) && !arg.starts_with("--"),
+            }
+        });
+        if !local {
+            forwarded.push(arg.clone());
+        }
+    }
+    forwarded
+}
+
+/// Runs a command on the host and returns its standard output, trimmed.
+///
+/// # Arguments
+///
+/// * `host` - The host, as given to `ssh`.
+/// * `command` - The command, run by the shell of the host.
+async fn ssh_output(host: &str, command: &str) -> Result<String> {
+    let output = Command::new("ssh")
+        .arg(host)
+        .arg(command)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Error running ssh")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        )
+        .context(format!("Error running `{}` on {}", command, host)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Writes a file on the host readable by its owner only, its content going through the
+/// standard input of `ssh` rather than its command line.
+///
+/// # Arguments
+///
+/// * `host` - The host, as given to `ssh`.
+/// * `path` - The path of the file on the host.
+/// * `content` - The content of the file.
+async fn write_file(host: &str, path: &str, content: &str) -> Result<()> {
+    let mut ssh = Command::new("ssh")
+        .arg(host)
+        .arg(format!("umask 077 && cat > {}", quote(path)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Error running ssh")?;
+    if let Some(mut stdin) = ssh.stdin.take() {
+        stdin.write_all(content.as_bytes()).await?;
+    }
+    let output = ssh.wait_with_output().await.context("Error running ssh")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        )
+        .context(format!("Error writing {} on {}", path, host)));
+    }
+    Ok(())
+}
+
+/// Copies a file or directory with `scp`.
+///
+/// # Arguments
+///
+/// * `from` - The source, `host:path` for a remote one.
+/// * `to` - The destination, `host:path` for a remote one.
+/// * `recursive` - Whether the source is a directory.
+async fn scp(from: &Path, to: &str, recursive: bool) -> Result<()> {
+    let mut scp = Command::new("scp");
+    scp.arg("-q");
+    if recursive {
+        scp.arg("-r");
+    }
+    let output = scp
+        .arg(from)
+        .arg(to)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Error running scp")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        )
+        .context(format!("Error copying {} to {}", from.display(), to)));
+    }
+    Ok(())
+}
+
+/// Quotes a word for the shell of the remote host.
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
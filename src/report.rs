@@ -0,0 +1,86 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use console::Term;
+use serde::Serialize;
+
+use crate::bench::QBench;
+use crate::util::{self, RunMetadata};
+use crate::QueryBenchResult;
+
+/// The page of the report, the results being embedded in place of its placeholder.
+const TEMPLATE: &str = include_str!("report.html");
+
+/// Where the results go in the template.
+const PLACEHOLDER: &str = "/*QBENCH_DATA*/null";
+
+/// The results embedded in the report.
+#[derive(Serialize)]
+struct ReportData<'a> {
+    metadata: &'a RunMetadata,
+    results: &'a [QueryBenchResult],
+    /// The groups of `--group-by`, empty when the benchmarks aren't grouped.
+    groups: Vec<ReportGroup>,
+}
+
+/// A group of benchmarks of the report, listed under its name.
+#[derive(Serialize)]
+struct ReportGroup {
+    name: String,
+    /// The indexes of its benchmarks in the results.
+    benches: Vec<usize>,
+    /// The aggregate statistics of its benchmarks.
+    summary: Vec<String>,
+}
+
+/// Writes the results to a self-contained HTML report, whose charts are drawn in the browser
+/// from the results embedded in it, so a run can be looked at without any other tool.
+///
+/// For each benchmark, the report charts the average latency of its revisions, the distribution
+/// of their latencies, and the latency of each iteration in the order they ran and over the
+/// wall-clock time of the run. With `--group-by`, the benchmarks are listed by group, each with
+/// the aggregate statistics of its benchmarks.
+///
+/// # Arguments
+///
+/// * `term` - Terminal interface for displaying progress and status messages.
+/// * `qbench` - Query benchmark configuration.
+/// * `bench_res` - Query benchmark results to be exported.
+/// * `metadata` - Description of the run.
+pub fn export(
+    term: &Term,
+    qbench: &QBench,
+    bench_res: &[QueryBenchResult],
+    metadata: &RunMetadata,
+) -> Result<()> {
+    term.write_line("Exporting results to an HTML report...")?;
+    let path = util::out_file(qbench)?;
+
+    let groups = match qbench.args.group_by {
+        Some(group_by) => util::group_results(bench_res, group_by)
+            .iter()
+            .map(|group| ReportGroup {
+                name: group.name.clone(),
+                benches: group
+                    .benches
+                    .iter()
+                    .filter_map(|bench| bench_res.iter().position(|b| std::ptr::eq(b, *bench)))
+                    .collect(),
+                summary: util::format_group_summary(group),
+            })
+            .collect(),
+        None => vec![],
+    };
+    let data = serde_json::to_string(&ReportData {
+        metadata,
+        results: bench_res,
+        groups,
+    })?;
+    // Keep the names of the benchmarks from closing the script they are embedded in
+    let html = TEMPLATE.replace(PLACEHOLDER, &data.replace("</", "<\\/"));
+    fs::write(&path, html).with_context(|| format!("Error writing {}", path))?;
+
+    term.clear_last_lines(1)?;
+    term.write_line(&format!("Results exported to {}.", path))?;
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sqlx::any::AnyConnectOptions;
+use sqlx::{query_as, Connection};
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{dialect, util, IoStats, TableSize, WaitEvent};
+
+/// Statistics of the database server taken at one point of the run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ServerSample {
+    /// Time elapsed since sampling started, in milliseconds.
+    pub elapsed_ms: u64,
+    /// The benchmarks running when the sample was taken.
+    pub running: Vec<String>,
+    /// The statistics, by name, e.g. `active_sessions` or `blks_read` on Postgres.
+    pub stats: BTreeMap<String, f64>,
+}
+
+/// Samples the statistics views of the database on a connection of its own until `stop` is
+/// cancelled, appending each sample to `samples`.
+///
+/// Sampling ends early, with a warning, on engines without statistics views or when a sample
+/// can't be taken, e.g. for lack of privileges, without affecting the run.
+///
+/// # Arguments
+///
+/// * `connect_options` - The options the connection is opened with.
+/// * `interval` - Time between two samples.
+/// * `running` - The benchmarks running at any time.
+/// * `samples` - The samples taken so far.
+/// * `stop` - Token ending the sampling when cancelled.
+pub(crate) async fn sample(
+    connect_options: AnyConnectOptions,
+    interval: Duration,
+    running: Arc<Mutex<BTreeSet<String>>>,
+    samples: Arc<Mutex<Vec<ServerSample>>>,
+    stop: CancellationToken,
+) {
+    let mut conn = match util::connect(&connect_options, None).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "could not connect to sample server statistics");
+            return;
+        }
+    };
+    let Some(statement) = dialect::server_stats(conn.kind()) else {
+        warn!(kind = ?conn.kind(), "server statistics can't be sampled on this database");
+        return;
+    };
+
+    let start = Instant::now();
+    let mut ticks = time::interval(interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = stop.cancelled() => break,
+        }
+        let rows: Vec<(String, String)> = match query_as(statement).fetch_all(&mut conn).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                warn!(error = %e, "could not sample server statistics");
+                break;
+            }
+        };
+        let sample = ServerSample {
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            running: running.lock().unwrap().iter().cloned().collect(),
+            stats: rows
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, value.trim().parse().ok()?)))
+                .collect(),
+        };
+        debug!(elapsed_ms = sample.elapsed_ms, "server statistics sampled");
+        samples.lock().unwrap().push(sample);
+    }
+    let _ = conn.close().await;
+}
+
+/// Snapshots the block read and hit counters of the user tables on a connection of its own.
+///
+/// Returns `None`, with a warning, on engines without these counters or when they can't be
+/// read, without affecting the run.
+///
+/// # Arguments
+///
+/// * `connect_options` - The options the connection is opened with.
+pub(crate) async fn io_snapshot(connect_options: &AnyConnectOptions) -> Option<IoStats> {
+    let mut conn = match util::connect(connect_options, None).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "could not connect to read I/O statistics");
+            return None;
+        }
+    };
+    let Some(statement) = dialect::io_stats(conn.kind()) else {
+        warn!(kind = ?conn.kind(), "I/O statistics can't be read on this database");
+        return None;
+    };
+    let row: Result<(i64, i64, i64, i64), _> = query_as(statement).fetch_one(&mut conn).await;
+    let _ = conn.close().await;
+    match row {
+        Ok((heap_blks_read, heap_blks_hit, idx_blks_read, idx_blks_hit)) => Some(IoStats {
+            heap_blks_read: heap_blks_read as u64,
+            heap_blks_hit: heap_blks_hit as u64,
+            idx_blks_read: idx_blks_read as u64,
+            idx_blks_hit: idx_blks_hit as u64,
+        }),
+        Err(e) => {
+            warn!(error = %e, "could not read I/O statistics");
+            None
+        }
+    }
+}
+
+/// Snapshots the counters of the blocks of the database read from disk and found in the buffer
+/// cache on a connection of its own, returning `(blocks read, blocks hit)`.
+///
+/// Returns `None`, with a warning, on engines without these counters or when they can't be
+/// read, without affecting the run.
+///
+/// # Arguments
+///
+/// * `connect_options` - The options the connection is opened with.
+pub(crate) async fn cache_snapshot(connect_options: &AnyConnectOptions) -> Option<(u64, u64)> {
+    let mut conn = match util::connect(connect_options, None).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "could not connect to read cache statistics");
+            return None;
+        }
+    };
+    let Some(statement) = dialect::cache_stats(conn.kind()) else {
+        warn!(kind = ?conn.kind(), "cache statistics can't be read on this database");
+        return None;
+    };
+    let row: Result<(i64, i64), _> = query_as(statement).fetch_one(&mut conn).await;
+    let _ = conn.close().await;
+    match row {
+        Ok((read, hit)) => Some((read as u64, hit as u64)),
+        Err(e) => {
+            warn!(error = %e, "could not read cache statistics");
+            None
+        }
+    }
+}
+
+/// Snapshots the sizes of tables on a connection of its own, leaving out the names that aren't
+/// tables.
+///
+/// Returns no size, with a warning, on engines not reporting the size of tables or when they
+/// can't be read, without affecting the run.
+///
+/// # Arguments
+///
+/// * `connect_options` - The options the connection is opened with.
+/// * `schema` - Schema unqualified names are resolved in.
+/// * `tables` - Names of the tables.
+pub(crate) async fn table_sizes(
+    connect_options: &AnyConnectOptions,
+    schema: Option<&str>,
+    tables: &[String],
+) -> Vec<TableSize> {
+    let mut conn = match util::connect(connect_options, schema).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "could not connect to read table sizes");
+            return vec![];
+        }
+    };
+    let mut sizes = vec![];
+    for table in tables {
+        let Some(statement) = dialect::table_size(conn.kind(), table) else {
+            warn!(kind = ?conn.kind(), "table sizes can't be read on this database");
+            break;
+        };
+        let row: Option<(String, i64, i64, i64)> =
+            match query_as(&statement).fetch_optional(&mut conn).await {
+                Ok(row) => row,
+                Err(e) => {
+                    warn!(table, error = %e, "could not read table size");
+                    continue;
+                }
+            };
+        if let Some((table, table_bytes, index_bytes, rows_estimate)) = row {
+            sizes.push(TableSize {
+                table,
+                table_bytes: table_bytes as u64,
+                index_bytes: index_bytes as u64,
+                rows_estimate: u64::try_from(rows_estimate).ok(),
+            });
+        }
+    }
+    let _ = conn.close().await;
+    sizes
+}
+
+/// Polls what the session `pid` is waiting on, on a connection of its own, until `stop` is
+/// cancelled, and returns the time spent on each wait event, the longest first.
+///
+/// A wait seen by a poll is counted for the whole time since the previous poll, so waits
+/// shorter than `interval` may be missed or overcounted.
+///
+/// # Arguments
+///
+/// * `connect_options` - The options the connection is opened with.
+/// * `pid` - Identifier of the session, as returned by [`dialect::backend_pid`].
+/// * `interval` - Time between two polls.
+/// * `stop` - Token ending the polling when cancelled.
+pub(crate) async fn sample_waits(
+    connect_options: AnyConnectOptions,
+    pid: i32,
+    interval: Duration,
+    stop: CancellationToken,
+) -> Vec<WaitEvent> {
+    let mut conn = match util::connect(&connect_options, None).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "could not connect to poll wait events");
+            return vec![];
+        }
+    };
+    let Some(statement) = dialect::wait_events(conn.kind(), pid) else {
+        return vec![];
+    };
+
+    let mut waits: Vec<WaitEvent> = vec![];
+    let mut last_poll = Instant::now();
+    let mut ticks = time::interval(interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = stop.cancelled() => break,
+        }
+        let rows: Vec<(String, String, Option<String>, Option<String>)> =
+            match query_as(&statement).fetch_all(&mut conn).await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    warn!(error = %e, "could not poll wait events");
+                    break;
+                }
+            };
+        let since_last_poll = last_poll.elapsed();
+        last_poll = Instant::now();
+        for (event_type, event, lock_type, relation) in rows {
+            let wait = WaitEvent {
+                event_type,
+                event,
+                lock_type,
+                relation,
+                duration: since_last_poll,
+            };
+            match waits.iter_mut().find(|w| w.is_same(&wait)) {
+                Some(w) => w.duration += since_last_poll,
+                None => waits.push(wait),
+            }
+        }
+    }
+    let _ = conn.close().await;
+
+    waits.sort_by_key(|w| Reverse(w.duration));
+    waits
+}
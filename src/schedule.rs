@@ -0,0 +1,393 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use console::{style, Term};
+use serde::Serialize;
+use tokio::time::{self, Instant};
+use url::Url;
+
+use crate::args::NotifyFormat;
+use crate::bench::QBench;
+use crate::history::{self, HistoryEntry};
+use crate::{util, QueryBenchResult, RevisionStatus};
+
+/// When the runs of a schedule start.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// A run starts this long after the start of the previous one, the first one right away.
+    Every(Duration),
+    /// A run starts on each time matching the cron expression, in UTC.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// Returns when the next run starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_start` - When the previous run started, if any.
+    fn next_start(&self, last_start: Option<Instant>) -> Instant {
+        match (self, last_start) {
+            (Schedule::Every(_), None) => Instant::now(),
+            // Runs overrunning the interval start the next one right away rather than catching up
+            (Schedule::Every(every), Some(last_start)) => (last_start + *every).max(Instant::now()),
+            (Schedule::Cron(schedule), _) => {
+                let wait = schedule
+                    .upcoming(Utc)
+                    .next()
+                    .and_then(|next| (next - Utc::now()).to_std().ok())
+                    .unwrap_or_default();
+                Instant::now() + wait
+            }
+        }
+    }
+}
+
+/// Parses a cron expression, the standard five fields (minute, hour, day of month, month and
+/// day of week) or the same with seconds first and an optional year last.
+///
+/// # Arguments
+///
+/// * `expr` - The cron expression, e.g. `0 */6 * * *`.
+pub fn parse_cron(expr: &str) -> Result<cron::Schedule> {
+    let expr = expr.trim();
+    let expr = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+    cron::Schedule::from_str(&expr).with_context(|| format!("Invalid cron expression: {}", expr))
+}
+
+/// A revision whose average latency grew beyond the threshold since the previous run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    pub bench: String,
+    pub revision: String,
+    pub before_ms: f64,
+    pub after_ms: f64,
+    /// Relative change of the average latency, in percent.
+    pub change_pct: f64,
+}
+
+/// Body of the notifications posted to the webhook.
+///
+/// The `text` field summarizes the rest, so the webhooks of most chat tools display it as is.
+#[derive(Debug, Serialize)]
+struct Notification<'a> {
+    text: String,
+    started_at: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    drifts: &'a [Drift],
+}
+
+/// A message of Slack's incoming webhooks, see <https://api.slack.com/messaging/webhooks>.
+#[derive(Debug, Serialize)]
+struct SlackMessage {
+    text: String,
+}
+
+/// Compares the average latencies of the revisions of a run with the previous one.
+///
+/// Revisions missing from either run, or without successful iterations in it, are left out.
+///
+/// # Arguments
+///
+/// * `previous` - Results of the previous run.
+/// * `current` - Results of the run.
+/// * `threshold` - Increase of the average latency, in percent, beyond which it counts as drift.
+pub fn detect_drift(
+    previous: &[QueryBenchResult],
+    current: &[QueryBenchResult],
+    threshold: f64,
+) -> Vec<Drift> {
+    let mut drifts = vec![];
+    for bench in current {
+        let Some(before_bench) = previous.iter().find(|b| b.name == bench.name) else {
+            continue;
+        };
+        for revision in &bench.results {
+            let Some(before) = before_bench
+                .results
+                .iter()
+                .find(|r| r.revision_name == revision.revision_name)
+            else {
+                continue;
+            };
+            if before.durations.is_empty() || revision.durations.is_empty() {
+                continue;
+            }
+            let before_ms = before.avg_query_duration.as_secs_f64() * 1000.0;
+            let after_ms = revision.avg_query_duration.as_secs_f64() * 1000.0;
+            if before_ms <= 0.0 {
+                continue;
+            }
+            let change_pct = (after_ms - before_ms) / before_ms * 100.0;
+            if change_pct > threshold {
+                drifts.push(Drift {
+                    bench: bench.name.clone(),
+                    revision: revision.revision_name.clone(),
+                    before_ms,
+                    after_ms,
+                    change_pct,
+                });
+            }
+        }
+    }
+    drifts
+}
+
+/// Reruns the suite on the schedule until interrupted, appending each complete run to the
+/// history file and posting to the webhook when a run fails or a revision drifted.
+///
+/// Runs are compared with the previous complete one, read from the history file at startup.
+///
+/// # Arguments
+///
+/// * `qbench` - The instance configured by the command line, config files are reloaded on
+///   each run.
+/// * `term` - Terminal the outcome of each run is reported on.
+/// * `schedule` - When the runs start.
+/// * `notify` - Webhook URL notified of failures and drift, if any.
+/// * `threshold` - Increase of the average latency, in percent, beyond which it counts as drift.
+pub async fn run(
+    qbench: &mut QBench,
+    term: &Term,
+    schedule: &Schedule,
+    notify: Option<&Url>,
+    threshold: f64,
+) -> Result<()> {
+    let cancel = qbench.cancellation_token();
+    let client = reqwest::Client::new();
+    let history_path = qbench.args.history.clone();
+    let mut previous = match &history_path {
+        Some(path) => history::load(path)?.pop().map(|entry| entry.results),
+        None => None,
+    };
+
+    let mut last_start = None;
+    loop {
+        let start = schedule.next_start(last_start);
+        if start > Instant::now() {
+            term.write_line(&format!(
+                "Next run in {}",
+                util::format_duration_pretty(&(start - Instant::now()))
+            ))?;
+        }
+        tokio::select! {
+            _ = time::sleep_until(start) => {}
+            _ = cancel.cancelled() => return Ok(()),
+        }
+        last_start = Some(start);
+
+        let started_at = util::timestamp();
+        term.write_line(&format!("Running benchmarks at {}...", started_at))?;
+        // A guardrail aborting a run cancels its own token only, the runs that follow go on
+        qbench.set_cancellation_token(cancel.child_token());
+        let bench_res = qbench.run_bench().await;
+        if cancel.is_cancelled() {
+            term.write_line(style("Run interrupted.").yellow().to_string().as_str())?;
+            return Ok(());
+        }
+
+        let (drifts, error) = match bench_res {
+            Ok(results) => {
+                let drifts = previous
+                    .as_deref()
+                    .map(|previous| detect_drift(previous, &results, threshold))
+                    .unwrap_or_default();
+                let failed = results
+                    .iter()
+                    .flat_map(|bench| &bench.results)
+                    .filter(|rev| {
+                        matches!(
+                            rev.status,
+                            RevisionStatus::Failed
+                                | RevisionStatus::TimedOut
+                                | RevisionStatus::RowLimitExceeded
+                        )
+                    })
+                    .count();
+                if let Some(path) = &history_path {
+                    let entry = HistoryEntry {
+                        started_at: started_at.clone(),
+                        results: results.clone(),
+                    };
+                    history::append(path, &entry)?;
+                }
+                previous = Some(results);
+                let error = match qbench.abort_reason() {
+                    Some(reason) => Some(format!("Run aborted, {}", reason)),
+                    None => (failed > 0).then(|| format!("{} revisions failed", failed)),
+                };
+                (drifts, error)
+            }
+            Err(e) => {
+                let message = format!("{:#}", anyhow::Error::from(e));
+                (vec![], Some(qbench.redact(&message).into_owned()))
+            }
+        };
+
+        let text = summary(&started_at, error.as_deref(), &drifts);
+        let line = if error.is_some() {
+            style(&text).red().to_string()
+        } else if !drifts.is_empty() {
+            style(&text).yellow().to_string()
+        } else {
+            text.clone()
+        };
+        term.write_line(&line)?;
+
+        if let Some(url) = notify {
+            if error.is_some() || !drifts.is_empty() {
+                let notification = Notification {
+                    text,
+                    started_at: &started_at,
+                    error,
+                    drifts: &drifts,
+                };
+                // A webhook being down shouldn't stop the runs that follow
+                let format = qbench.args.notify_format;
+                if let Err(e) = post(&client, url, format, &notification).await {
+                    tracing::warn!(error = %format!("{:#}", e), "notification failed");
+                    term.write_line(style(format!("{:#}", e)).yellow().to_string().as_str())?;
+                }
+            }
+        }
+    }
+}
+
+/// Summarizes the outcome of a run in one line, e.g. `qbench run of ...: 2 revisions drifted
+/// (users/v2 +35.2%, orders/v1 +21.0%)`.
+///
+/// # Arguments
+///
+/// * `started_at` - When the run started.
+/// * `error` - Why the run failed, if it did.
+/// * `drifts` - The revisions that drifted.
+fn summary(started_at: &str, error: Option<&str>, drifts: &[Drift]) -> String {
+    let mut text = format!("qbench run of {}: ", started_at);
+    if let Some(error) = error {
+        text.push_str(error);
+        if !drifts.is_empty() {
+            text.push_str(", ");
+        }
+    }
+    if !drifts.is_empty() {
+        let revisions: Vec<String> = drifts
+            .iter()
+            .map(|d| format!("{}/{} {:+.1}%", d.bench, d.revision, d.change_pct))
+            .collect();
+        text.push_str(&format!(
+            "{} revisions drifted ({})",
+            drifts.len(),
+            revisions.join(", ")
+        ));
+    } else if error.is_none() {
+        text.push_str("no drift");
+    }
+    text
+}
+
+/// Posts the outcome of a run to a webhook once it completed, whether it failed, regressed or
+/// not, unlike the runs of a schedule which only notify of failures and drift.
+///
+/// # Arguments
+///
+/// * `url` - The webhook URL.
+/// * `format` - The layout of the message.
+/// * `started_at` - When the run started.
+/// * `error` - Why the run failed, if it did.
+/// * `drifts` - The revisions that regressed since the previous run.
+/// * `overview` - The overview of the run, as printed after its results.
+pub async fn notify(
+    url: &Url,
+    format: NotifyFormat,
+    started_at: &str,
+    error: Option<String>,
+    drifts: &[Drift],
+    overview: Option<&str>,
+) -> Result<()> {
+    let mut text = summary(started_at, error.as_deref(), drifts);
+    if let Some(overview) = overview {
+        text = format!("{}\n{}", text, overview);
+    }
+    let notification = Notification {
+        text,
+        started_at,
+        error,
+        drifts,
+    };
+    post(&reqwest::Client::new(), url, format, &notification).await
+}
+
+/// Formats a notification as a Slack message, with an icon telling its outcome at a glance and
+/// the drifted revisions as a list.
+///
+/// # Arguments
+///
+/// * `notification` - The notification.
+fn slack_message(notification: &Notification) -> SlackMessage {
+    // Slack reads these three as control characters, see
+    // <https://api.slack.com/reference/surfaces/formatting#escaping>
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let icon = match (&notification.error, notification.drifts.is_empty()) {
+        (Some(_), _) => ":x:",
+        (None, false) => ":warning:",
+        (None, true) => ":white_check_mark:",
+    };
+    let mut lines = notification.text.lines();
+    let mut text = format!(
+        "{} *{}*",
+        icon,
+        escape(lines.next().unwrap_or_default())
+    );
+    for drift in notification.drifts {
+        text.push_str(&format!(
+            "\n• `{}/{}` {:+.1}% ({:.3}ms → {:.3}ms)",
+            escape(&drift.bench),
+            escape(&drift.revision),
+            drift.change_pct,
+            drift.before_ms,
+            drift.after_ms
+        ));
+    }
+    for line in lines {
+        text.push_str(&format!("\n{}", escape(line)));
+    }
+    SlackMessage { text }
+}
+
+/// Posts a notification to the webhook.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client.
+/// * `url` - The webhook URL.
+/// * `format` - The layout of the message.
+/// * `notification` - The notification.
+async fn post(
+    client: &reqwest::Client,
+    url: &Url,
+    format: NotifyFormat,
+    notification: &Notification<'_>,
+) -> Result<()> {
+    let request = match format {
+        NotifyFormat::Json => client.post(url.clone()).json(notification),
+        NotifyFormat::Slack => client.post(url.clone()).json(&slack_message(notification)),
+    };
+    request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(reqwest::Error::without_url)
+        .with_context(|| format!("Error notifying {}", util::redact_url(url.as_str())))?;
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use sqlx::any::AnyKind;
+use sqlx::{query, AnyConnection, Connection};
+use tokio::time::{Duration, Instant};
+
+use crate::{dialect, fixture};
+
+/// Number of generated rows loaded at once.
+const CHUNK_ROWS: usize = 10_000;
+
+/// Describes the synthetic data generated by `qbench seed`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SeedSpec {
+    /// Seed of the random generator, making the generated data reproducible.
+    pub seed: Option<u64>,
+    pub tables: Vec<TableSpec>,
+}
+
+/// A table filled with generated rows. The table must already exist.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TableSpec {
+    pub name: String,
+    pub rows: usize,
+    pub columns: Vec<ColumnSpec>,
+}
+
+/// A column of a seeded table and how its values are generated.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ColumnSpec {
+    pub name: String,
+    /// Fraction of the rows, between 0 and 1, where the column is `NULL`.
+    #[serde(default)]
+    pub null_ratio: f64,
+    #[serde(flatten)]
+    pub kind: ColumnKind,
+}
+
+/// Kinds of generated column values.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ColumnKind {
+    /// Consecutive integers, e.g. for primary keys.
+    Sequence {
+        #[serde(default = "default_start")]
+        start: i64,
+    },
+    /// Integers between `min` and `max`, both included.
+    Int {
+        min: i64,
+        max: i64,
+        #[serde(default)]
+        distribution: Distribution,
+    },
+    /// Decimal numbers between `min` and `max`.
+    Float {
+        min: f64,
+        max: f64,
+        #[serde(default)]
+        distribution: Distribution,
+    },
+    /// Text values picked among `cardinality` distinct values.
+    Text {
+        cardinality: usize,
+        #[serde(default)]
+        distribution: Distribution,
+    },
+    /// Booleans, `true` for the given fraction of the rows.
+    Bool {
+        #[serde(default = "default_true_ratio")]
+        true_ratio: f64,
+    },
+}
+
+/// How generated values are spread over their range.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Distribution {
+    /// Every value is equally likely.
+    #[default]
+    Uniform,
+    /// Values at the start of the range are far more frequent, like hot keys.
+    Skewed,
+}
+
+fn default_start() -> i64 {
+    1
+}
+
+fn default_true_ratio() -> f64 {
+    0.5
+}
+
+impl Distribution {
+    /// Picks a position between 0 (included) and 1 (excluded) following the distribution.
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        let position: f64 = rng.gen();
+        match self {
+            Distribution::Uniform => position,
+            Distribution::Skewed => position.powi(4),
+        }
+    }
+}
+
+impl ColumnSpec {
+    /// Generates the CSV value of the column for the given row, an empty value meaning `NULL`.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Index of the row.
+    /// * `rng` - The random generator of the spec.
+    /// * `kind` - The database engine the value is loaded into.
+    fn generate(&self, row: usize, rng: &mut StdRng, kind: AnyKind) -> String {
+        if self.null_ratio > 0.0 && rng.gen_bool(self.null_ratio.min(1.0)) {
+            return String::new();
+        }
+        match &self.kind {
+            ColumnKind::Sequence { start } => (start + row as i64).to_string(),
+            ColumnKind::Int { min, max, distribution } => {
+                // Computed on 128 bits, the span of the whole i64 range doesn't fit in one
+                let span = (*max as i128 - *min as i128 + 1) as f64;
+                let value = *min as i128 + (distribution.sample(rng) * span) as i128;
+                value.min(*max as i128).to_string()
+            }
+            ColumnKind::Float { min, max, distribution } => {
+                format!("{:.2}", min + distribution.sample(rng) * (max - min))
+            }
+            ColumnKind::Text { cardinality, distribution } => {
+                let value = (distribution.sample(rng) * *cardinality as f64) as usize;
+                format!("{}_{}", self.name, value)
+            }
+            ColumnKind::Bool { true_ratio } => {
+                let value = rng.gen_bool(true_ratio.clamp(0.0, 1.0));
+                // MySQL booleans are TINYINT(1), which don't take 'true' and 'false'
+                match kind {
+                    AnyKind::MySql => u8::from(value).to_string(),
+                    _ => value.to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Reads and validates a seed spec file.
+///
+/// # Arguments
+///
+/// * `path` - Path of the TOML spec file.
+pub fn load_spec(path: &Path) -> Result<SeedSpec> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Error reading seed spec {}", path.display()))?;
+    let spec: SeedSpec = toml::from_str(&content)
+        .with_context(|| format!("Invalid seed spec {}", path.display()))?;
+
+    for table in &spec.tables {
+        for column in &table.columns {
+            let valid = match column.kind {
+                ColumnKind::Int { min, max, .. } => min <= max,
+                ColumnKind::Float { min, max, .. } => min <= max,
+                ColumnKind::Text { cardinality, .. } => cardinality > 0,
+                _ => true,
+            };
+            if !valid {
+                return Err(anyhow!(
+                    "Invalid range for column {} of table {}",
+                    column.name,
+                    table.name
+                ));
+            }
+        }
+    }
+    Ok(spec)
+}
+
+/// Generates the rows of a table and loads them in chunks, inside a transaction committed
+/// once the whole table is loaded.
+///
+/// Returns the time it took to generate and load the rows.
+///
+/// # Arguments
+///
+/// * `conn` - The connection the rows are loaded with.
+/// * `table` - The table to seed.
+/// * `rng` - The random generator values are drawn from.
+/// * `truncate` - Whether existing rows are deleted first.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn example(mut conn: sqlx::AnyConnection) -> anyhow::Result<()> {
+/// use std::path::Path;
+///
+/// use qbench::seed::{load_spec, seed_table};
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+///
+/// let spec = load_spec(Path::new("seed.toml"))?;
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let duration = seed_table(&mut conn, &spec.tables[0], &mut rng, false).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn seed_table(
+    conn: &mut AnyConnection,
+    table: &TableSpec,
+    rng: &mut StdRng,
+    truncate: bool,
+) -> Result<Duration> {
+    let start = Instant::now();
+    let kind = conn.kind();
+    let mut tx = conn.begin().await?;
+
+    if truncate {
+        let name = dialect::quote_identifier(kind, &table.name);
+        let _ = query(&format!("DELETE FROM {}", name))
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let header: Vec<&str> = table.columns.iter().map(|c| c.name.as_str()).collect();
+    let mut loaded = 0;
+    while loaded < table.rows {
+        // Generate the next chunk as CSV, so it can be streamed with COPY where supported
+        let chunk = CHUNK_ROWS.min(table.rows - loaded);
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record(&header)?;
+        for row in loaded..loaded + chunk {
+            let record: Vec<String> =
+                table.columns.iter().map(|c| c.generate(row, rng, kind)).collect();
+            writer.write_record(&record)?;
+        }
+        let content = String::from_utf8(writer.into_inner()?)?;
+
+        fixture::load_csv(&mut tx, &table.name, &content)
+            .await
+            .with_context(|| format!("Error seeding table {}", table.name))?;
+        loaded += chunk;
+    }
+
+    tx.commit().await?;
+    Ok(start.elapsed())
+}
+
+/// Creates the random generator of a spec, seeded with its `seed` when there is one.
+pub fn rng(spec: &SeedSpec) -> StdRng {
+    match spec.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(kind: ColumnKind) -> ColumnSpec {
+        ColumnSpec { name: "c".to_string(), null_ratio: 0.0, kind }
+    }
+
+    #[test]
+    fn ints_stay_between_min_and_max() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for (min, max) in [(-3, 3), (5, 5), (i64::MIN, i64::MAX), (i64::MAX - 1, i64::MAX)] {
+            let column = column(ColumnKind::Int { min, max, distribution: Distribution::Uniform });
+            for row in 0..1000 {
+                let value = column.generate(row, &mut rng, AnyKind::Postgres);
+                let value: i64 = value.parse().unwrap();
+                assert!((min..=max).contains(&value), "{} out of {}..={}", value, min, max);
+            }
+        }
+    }
+
+    #[test]
+    fn bools_are_numbers_on_mysql() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let column = column(ColumnKind::Bool { true_ratio: 1.0 });
+        assert_eq!(column.generate(0, &mut rng, AnyKind::MySql), "1");
+        assert_eq!(column.generate(0, &mut rng, AnyKind::Postgres), "true");
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+use crate::args::Shard;
+use crate::bench::QBench;
+use crate::history::{self, HistoryEntry};
+use crate::util;
+use crate::QueryBenchResult;
+
+/// States of a run triggered through the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Failed,
+    Interrupted,
+}
+
+/// Status of a run, as returned by the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub id: u64,
+    pub status: RunStatus,
+    /// When the run started, in RFC 3339 format.
+    pub started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    /// Number of benchmarks completed so far.
+    pub completed: usize,
+    /// The error ending the run, for failed runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Body of a request starting a run, narrowing down the benchmarks selected on the command
+/// line of the server.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunRequest {
+    /// Glob pattern the benchmark names must match, like `--bench`.
+    pub bench: Option<String>,
+    /// Glob patterns the suite of the benchmarks must match, like `--suite`.
+    #[serde(default)]
+    pub suites: Vec<String>,
+    /// Glob pattern the revision names must match, like `--revision`.
+    pub revision: Option<String>,
+    /// Only run the revisions with one of these tags, like `--tags`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Number of iterations of each revision, like `--iterations`.
+    pub iterations: Option<usize>,
+    /// Only run this share of the iterations, like `--shard`.
+    pub shard: Option<Shard>,
+}
+
+/// A run triggered through the API, with the results of the benchmarks completed so far.
+struct Run {
+    summary: RunSummary,
+    results: Vec<QueryBenchResult>,
+    cancel: CancellationToken,
+}
+
+/// State shared by the handlers.
+#[derive(Clone)]
+struct ServerState {
+    /// The instance configured by the command line, runs reuse its pool.
+    qbench: QBench,
+    runs: Arc<Mutex<BTreeMap<u64, Run>>>,
+}
+
+/// An error response, returned as `{"error": "..."}`.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Serves the REST API until the process is stopped.
+///
+/// * `POST /runs` starts a run of the suite, optionally narrowed down by a [`RunRequest`] body,
+///   unless one is already running.
+/// * `GET /runs` lists the runs and `GET /runs/{id}` returns the status of one.
+/// * `GET /runs/{id}/results` returns the results of the benchmarks completed so far.
+/// * `POST /runs/{id}/cancel` interrupts a run.
+/// * `GET /history` returns the runs of the history file, plotted by the dashboard at `/`.
+///
+/// # Arguments
+///
+/// * `qbench` - The instance configured by the command line.
+/// * `listen` - The address to listen on.
+pub async fn serve(qbench: QBench, listen: SocketAddr) -> Result<()> {
+    let state = ServerState {
+        qbench,
+        runs: Arc::new(Mutex::new(BTreeMap::new())),
+    };
+    let app = Router::new()
+        .route("/", get(dashboard))
+        .route("/history", get(get_history))
+        .route("/runs", get(list_runs).post(start_run))
+        .route("/runs/{id}", get(get_run))
+        .route("/runs/{id}/results", get(get_results))
+        .route("/runs/{id}/cancel", post(cancel_run))
+        .with_state(state);
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Error listening on {}", listen))?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+/// Starts a run in the background and returns its status.
+async fn start_run(
+    State(state): State<ServerState>,
+    request: Option<Json<RunRequest>>,
+) -> Result<(StatusCode, Json<RunSummary>), ApiError> {
+    let Json(request) = request.unwrap_or_default();
+    let mut args = (*state.qbench.args).clone();
+    if request.bench.is_some() {
+        args.bench = request.bench;
+    }
+    if !request.suites.is_empty() {
+        args.suites = request.suites;
+    }
+    if request.revision.is_some() {
+        args.revision = request.revision;
+    }
+    if !request.tags.is_empty() {
+        args.tags = request.tags;
+    }
+    if let Some(iterations) = request.iterations {
+        args.iterations = iterations;
+    }
+    if request.shard.is_some() {
+        args.shard = request.shard;
+    }
+    let mut qbench = QBench::with_pool(state.qbench.pool().clone(), args, false).map_err(|e| {
+        let message = state.qbench.redact(&format!("{:#}", anyhow::Error::from(e))).into_owned();
+        ApiError(StatusCode::BAD_REQUEST, message)
+    })?;
+    let cancel = CancellationToken::new();
+    qbench.set_cancellation_token(cancel.clone());
+
+    // Runs at the same time would skew each other's latencies
+    let summary = {
+        let mut runs = state.runs.lock().unwrap();
+        if runs.values().any(|run| run.summary.status == RunStatus::Running) {
+            return Err(ApiError(
+                StatusCode::CONFLICT,
+                "A run is already in progress".to_string(),
+            ));
+        }
+        let id = runs.keys().next_back().map_or(1, |id| id + 1);
+        let summary = RunSummary {
+            id,
+            status: RunStatus::Running,
+            started_at: util::timestamp(),
+            finished_at: None,
+            completed: 0,
+            error: None,
+        };
+        runs.insert(
+            id,
+            Run {
+                summary: summary.clone(),
+                results: vec![],
+                cancel,
+            },
+        );
+        summary
+    };
+
+    let runs = state.runs.clone();
+    let id = summary.id;
+    tokio::spawn(async move {
+        let mut results = qbench.run_bench_stream();
+        while let Some(result) = results.next().await {
+            let mut guard = runs.lock().unwrap();
+            let Some(run) = guard.get_mut(&id) else {
+                return;
+            };
+            match result {
+                Ok(result) => {
+                    run.results.push(result);
+                    run.summary.completed += 1;
+                }
+                Err(e) => {
+                    let message = format!("{:#}", anyhow::Error::from(e));
+                    run.summary.error = Some(qbench.redact(&message).into_owned());
+                }
+            }
+        }
+
+        if let Some(run) = runs.lock().unwrap().get_mut(&id) {
+            run.summary.status = if run.summary.error.is_some() {
+                RunStatus::Failed
+            } else if qbench.is_interrupted() {
+                RunStatus::Interrupted
+            } else {
+                RunStatus::Completed
+            };
+            run.summary.finished_at = Some(util::timestamp());
+
+            // Only complete runs are comparable with the others of the history
+            if let (RunStatus::Completed, Some(path)) = (run.summary.status, &qbench.args.history) {
+                let entry = HistoryEntry {
+                    started_at: run.summary.started_at.clone(),
+                    results: run.results.clone(),
+                };
+                if let Err(e) = history::append(path, &entry) {
+                    run.summary.error = Some(qbench.redact(&format!("{:#}", e)).into_owned());
+                }
+            }
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(summary)))
+}
+
+/// Lists the runs, oldest first.
+async fn list_runs(State(state): State<ServerState>) -> Json<Vec<RunSummary>> {
+    let runs = state.runs.lock().unwrap();
+    Json(runs.values().map(|run| run.summary.clone()).collect())
+}
+
+/// Returns the status of a run.
+async fn get_run(
+    State(state): State<ServerState>,
+    Path(id): Path<u64>,
+) -> Result<Json<RunSummary>, ApiError> {
+    let runs = state.runs.lock().unwrap();
+    let run = runs.get(&id).ok_or_else(|| not_found(id))?;
+    Ok(Json(run.summary.clone()))
+}
+
+/// Returns the results of the benchmarks a run completed so far.
+async fn get_results(
+    State(state): State<ServerState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Vec<QueryBenchResult>>, ApiError> {
+    let runs = state.runs.lock().unwrap();
+    let run = runs.get(&id).ok_or_else(|| not_found(id))?;
+    Ok(Json(run.results.clone()))
+}
+
+/// Interrupts a run, which keeps the results of the benchmarks completed so far.
+async fn cancel_run(
+    State(state): State<ServerState>,
+    Path(id): Path<u64>,
+) -> Result<(StatusCode, Json<RunSummary>), ApiError> {
+    let runs = state.runs.lock().unwrap();
+    let run = runs.get(&id).ok_or_else(|| not_found(id))?;
+    run.cancel.cancel();
+    Ok((StatusCode::ACCEPTED, Json(run.summary.clone())))
+}
+
+/// Returns the error of a request for an unknown run.
+fn not_found(id: u64) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, format!("No run with id {}", id))
+}
+
+/// Serves the dashboard plotting the history file.
+async fn dashboard() -> Html<&'static str> {
+    Html(include_str!("dashboard.html"))
+}
+
+/// Returns the runs recorded in the history file given with `--history`, oldest first.
+async fn get_history(
+    State(state): State<ServerState>,
+) -> Result<Json<Vec<HistoryEntry>>, ApiError> {
+    let entries = match &state.qbench.args.history {
+        Some(path) => history::load(path)
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?,
+        None => vec![],
+    };
+    Ok(Json(entries))
+}
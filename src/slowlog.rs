@@ -0,0 +1,259 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use regex::{Captures, Regex};
+use tokio::time::Duration;
+
+use crate::capture::CapturedStatement;
+
+/// A statement found in a slow-query log, with the values it was executed with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedStatement {
+    pub query: String,
+    pub duration: Duration,
+}
+
+/// Parses the statements of a slow-query log, in the order they were logged.
+///
+/// Both the Postgres log of `log_min_duration_statement`, in the `stderr` format, and the MySQL
+/// slow query log are recognized. The parameters the server logs for prepared statements are
+/// substituted into them.
+///
+/// # Arguments
+///
+/// * `log` - The content of the log file.
+pub fn parse_log(log: &str) -> Vec<LoggedStatement> {
+    if log.lines().any(|line| line.starts_with("# Query_time:")) {
+        parse_mysql_log(log)
+    } else {
+        parse_postgres_log(log)
+    }
+}
+
+/// Parses the `duration: ... ms  statement: ...` entries of a Postgres log, continued on the
+/// lines starting with a tab, and the parameters of the `DETAIL` line of prepared statements.
+fn parse_postgres_log(log: &str) -> Vec<LoggedStatement> {
+    let entry = Regex::new(r"duration: ([\d.]+) ms\s+(?:statement|execute [^:]*): (.*)$");
+    let entry = entry.unwrap();
+    let parameters = Regex::new(r"DETAIL:\s+[Pp]arameters: (.*)$").unwrap();
+    let parameter = Regex::new(r"\$(\d+) = ('(?:[^']|'')*'|NULL)").unwrap();
+    let placeholder = Regex::new(r"\$(\d+)").unwrap();
+
+    let mut statements: Vec<LoggedStatement> = vec![];
+    // Whether the previous lines belong to the last statement
+    let mut continued = false;
+    for line in log.lines() {
+        if let Some(captures) = entry.captures(line) {
+            let millis: f64 = captures[1].parse().unwrap_or_default();
+            statements.push(LoggedStatement {
+                query: captures[2].to_string(),
+                duration: Duration::from_secs_f64(millis / 1000.0),
+            });
+            continued = true;
+        } else if let (true, Some(last)) = (continued, statements.last_mut()) {
+            if let Some(rest) = line.strip_prefix('\t') {
+                last.query.push('\n');
+                last.query.push_str(rest);
+            } else if let Some(captures) = parameters.captures(line) {
+                let values: HashMap<&str, &str> = parameter
+                    .captures_iter(captures.get(1).unwrap().as_str())
+                    .map(|c| (c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str()))
+                    .collect();
+                last.query = placeholder
+                    .replace_all(&last.query, |c: &Captures| match values.get(&c[1]) {
+                        Some(value) => value.to_string(),
+                        None => c[0].to_string(),
+                    })
+                    .into_owned();
+                continued = false;
+            } else {
+                continued = false;
+            }
+        }
+    }
+    for statement in &mut statements {
+        statement.query = statement.query.trim().to_string();
+    }
+    statements
+}
+
+/// Parses the entries of a MySQL slow query log, each a `# Query_time: ...` header followed by
+/// the statement, preceded by the `use` and `SET timestamp` statements of the server.
+fn parse_mysql_log(log: &str) -> Vec<LoggedStatement> {
+    let header = Regex::new(r"^# Query_time: ([\d.]+)").unwrap();
+
+    let mut statements: Vec<LoggedStatement> = vec![];
+    let mut current: Option<LoggedStatement> = None;
+    for line in log.lines() {
+        if let Some(captures) = header.captures(line) {
+            statements.extend(current.take());
+            let seconds: f64 = captures[1].parse().unwrap_or_default();
+            current = Some(LoggedStatement {
+                query: String::new(),
+                duration: Duration::from_secs_f64(seconds),
+            });
+        } else if line.starts_with('#') {
+            statements.extend(current.take());
+        } else if let Some(statement) = &mut current {
+            let lowercase = line.trim_start().to_lowercase();
+            if statement.query.is_empty()
+                && (lowercase.starts_with("use ") || lowercase.starts_with("set timestamp="))
+            {
+                continue;
+            }
+            statement.query.push_str(line);
+            statement.query.push('\n');
+        }
+    }
+    statements.extend(current);
+    statements.retain(|s| !s.query.trim().is_empty());
+    for statement in &mut statements {
+        statement.query = statement.query.trim().to_string();
+    }
+    statements
+}
+
+/// Normalizes a statement, replacing its literals and parameters with `?` and collapsing its
+/// whitespace, so executions with different values are grouped together.
+///
+/// # Arguments
+///
+/// * `query` - The statement as it was executed.
+///
+/// # Example
+///
+/// ```
+/// use qbench::slowlog::normalize;
+///
+/// let normalized = normalize("SELECT * FROM users WHERE id IN (1, 2)  AND name = 'bob';");
+/// assert_eq!(normalized, "select * from users where id in (?) and name = ?");
+/// ```
+pub fn normalize(query: &str) -> String {
+    let literals = Regex::new(r"'(?:[^']|'')*'|\$\d+|\b\d+(?:\.\d+)?\b").unwrap();
+    let lists = Regex::new(r"\(\s*\?(?:\s*,\s*\?)*\s*\)").unwrap();
+    let whitespace = Regex::new(r"\s+").unwrap();
+    let normalized = literals.replace_all(query, "?");
+    let normalized = lists.replace_all(&normalized, "(?)");
+    let normalized = whitespace.replace_all(&normalized, " ");
+    normalized
+        .trim()
+        .trim_end_matches(';')
+        .trim_end()
+        .to_lowercase()
+}
+
+/// Groups logged statements by their [`normalize`]d form, the groups that took the most time in
+/// total first. Each group is represented by the execution of median duration, keeping values
+/// the statement was actually run with.
+///
+/// # Arguments
+///
+/// * `statements` - The statements parsed from the log.
+/// * `top` - The maximum number of groups returned.
+pub fn aggregate(statements: Vec<LoggedStatement>, top: usize) -> Vec<CapturedStatement> {
+    let mut groups: Vec<Vec<LoggedStatement>> = vec![];
+    let mut indexes: HashMap<String, usize> = HashMap::new();
+    for statement in statements {
+        let index = *indexes
+            .entry(normalize(&statement.query))
+            .or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+        groups[index].push(statement);
+    }
+    let total = |group: &Vec<LoggedStatement>| group.iter().map(|s| s.duration).sum::<Duration>();
+    groups.sort_by_key(|group| Reverse(total(group)));
+    groups
+        .into_iter()
+        .take(top)
+        .map(|mut group| {
+            group.sort_by_key(|s| s.duration);
+            let mean = total(&group) / group.len() as u32;
+            let calls = group.len() as u64;
+            let median = group.swap_remove(group.len() / 2);
+            CapturedStatement {
+                query: median.query,
+                calls,
+                mean,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement(query: &str, millis: f64) -> LoggedStatement {
+        LoggedStatement {
+            query: query.to_string(),
+            duration: Duration::from_secs_f64(millis / 1000.0),
+        }
+    }
+
+    #[test]
+    fn parses_postgres_statements() {
+        let log = "\
+2024-01-05 10:00:00.123 UTC [123] LOG:  duration: 12.500 ms  statement: SELECT *
+\tFROM users WHERE id = 1
+2024-01-05 10:00:00.500 UTC [123] LOG:  checkpoint starting: time
+\tnot a continuation
+2024-01-05 10:00:01.000 UTC [124] LOG:  duration: 3.000 ms  statement: SELECT 1;
+";
+        assert_eq!(
+            parse_log(log),
+            vec![
+                statement("SELECT *\nFROM users WHERE id = 1", 12.5),
+                statement("SELECT 1;", 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn substitutes_the_parameters_of_prepared_statements() {
+        let log = "\
+2024-01-05 10:00:01.000 UTC [123] LOG:  duration: 0.250 ms  execute <unnamed>: SELECT * FROM orders WHERE id = $1 AND note = $2 AND ref = $10
+2024-01-05 10:00:01.000 UTC [123] DETAIL:  Parameters: $1 = '42', $2 = 'it''s', $10 = NULL
+";
+        assert_eq!(
+            parse_log(log),
+            vec![statement(
+                "SELECT * FROM orders WHERE id = '42' AND note = 'it''s' AND ref = NULL",
+                0.25
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_mysql_statements() {
+        let log = "\
+/usr/sbin/mysqld, Version: 8.0.35. started with:
+# Time: 2024-01-05T10:00:00.000000Z
+# User@Host: app[app] @ localhost []  Id:     8
+# Query_time: 2.500000  Lock_time: 0.000100 Rows_sent: 1  Rows_examined: 1000
+use shop;
+SET timestamp=1704448800;
+SELECT * FROM users
+WHERE id = 1;
+# Time: 2024-01-05T10:00:01.000000Z
+# User@Host: app[app] @ localhost []  Id:     8
+# Query_time: 0.010000  Lock_time: 0.000000 Rows_sent: 0  Rows_examined: 1
+SET timestamp=1704448801;
+UPDATE users SET seen = 1 WHERE id = 1;
+";
+        assert_eq!(
+            parse_log(log),
+            vec![
+                statement("SELECT * FROM users\nWHERE id = 1;", 2500.0),
+                statement("UPDATE users SET seen = 1 WHERE id = 1;", 10.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_logs_without_statements() {
+        assert!(parse_log("").is_empty());
+        assert!(parse_log("2024-01-05 10:00:00 UTC [1] LOG:  database system is ready").is_empty());
+    }
+}
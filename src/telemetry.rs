@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::{Layer, Registry};
+
+use crate::QueryBenchResult;
+
+/// Exports the spans of a run and the latencies of its revisions to an OpenTelemetry collector
+/// over OTLP/HTTP, so runs show up next to the traces of the applications they benchmark.
+pub struct Telemetry {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Telemetry {
+    /// Creates the exporters sending to the collector at the given endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - Base URL of the collector's OTLP/HTTP receiver, e.g.
+    ///   `http://localhost:4318`.
+    pub fn init(endpoint: &str) -> Result<Self> {
+        let endpoint = endpoint.trim_end_matches('/');
+        let resource = Resource::builder().with_service_name("qbench").build();
+
+        let span_exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint))
+            .build()
+            .context("Error creating the OTLP span exporter")?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(span_exporter)
+            .with_resource(resource.clone())
+            .build();
+
+        let metric_exporter = MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/metrics", endpoint))
+            .build()
+            .context("Error creating the OTLP metric exporter")?;
+        let meter_provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(metric_exporter)
+            .with_resource(resource)
+            .build();
+
+        Ok(Self {
+            tracer_provider,
+            meter_provider,
+        })
+    }
+
+    /// Returns the layer turning the spans of the runner, down to each iteration, into
+    /// OpenTelemetry spans.
+    pub fn layer(&self) -> Box<dyn Layer<Registry> + Send + Sync> {
+        let tracer = self.tracer_provider.tracer("qbench");
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(Targets::new().with_target("qbench", LevelFilter::DEBUG))
+            .boxed()
+    }
+
+    /// Records the iteration latencies of each revision in the `qbench.query.duration`
+    /// histogram, in milliseconds, with the benchmark, revision and status as attributes.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - The results of the run.
+    pub fn record_results(&self, results: &[QueryBenchResult]) {
+        let histogram = self
+            .meter_provider
+            .meter("qbench")
+            .f64_histogram("qbench.query.duration")
+            .with_unit("ms")
+            .with_description("Duration of the iterations of a revision")
+            .build();
+        for bench in results {
+            for revision in &bench.results {
+                let attributes = [
+                    KeyValue::new("bench", bench.name.clone()),
+                    KeyValue::new("revision", revision.revision_name.clone()),
+                    KeyValue::new("status", revision.status.to_string()),
+                ];
+                for duration in &revision.durations {
+                    histogram.record(duration.as_secs_f64() * 1000.0, &attributes);
+                }
+            }
+        }
+    }
+
+    /// Flushes what wasn't exported yet and stops the exporters.
+    pub fn shutdown(self) -> Result<()> {
+        self.tracer_provider
+            .shutdown()
+            .context("Error exporting spans to the collector")?;
+        self.meter_provider
+            .shutdown()
+            .context("Error exporting metrics to the collector")?;
+        Ok(())
+    }
+}